@@ -1,6 +1,7 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput, Data, Fields, FieldsNamed, Token};
+use syn::{ImplItem, ItemImpl, Meta, Expr, Lit};
 use syn::punctuated::Punctuated;
 use proc_macro2::TokenStream as TokenStream2;
 
@@ -162,4 +163,144 @@ fn rust_type_to_ts(ty: &syn::Type) -> proc_macro2::TokenStream {
         },
         _ => quote!("any"),
     }
-} 
\ No newline at end of file
+}
+
+/// Implements the boilerplate of a queue `Job` and registers it with the worker.
+///
+/// Place it above `#[async_trait]` on the `impl Job for ...` block. It generates `serialize`,
+/// `deserialize` and `type_name`, plus `queue`, `tries`, `delay`, `backoff` and `timeout`
+/// from the attribute arguments, skipping any method the impl already defines. A bare
+/// `encrypted` argument encrypts the job's data while it is queued.
+///
+/// ```rust,ignore
+/// #[job(queue = "emails", tries = 3, delay = 10, backoff = [10, 60], timeout = 120, encrypted)]
+/// #[async_trait]
+/// impl Job for SendEmailJob {
+///     async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+///         Ok(())
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn job(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with Punctuated::<Meta, Token![,]>::parse_terminated);
+    let mut input = parse_macro_input!(item as ItemImpl);
+
+    match expand_job(args, &mut input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => TokenStream::from(error.to_compile_error()),
+    }
+}
+
+fn expand_job(args: Punctuated<Meta, Token![,]>, input: &mut ItemImpl) -> syn::Result<TokenStream2> {
+    let self_ty = input.self_ty.clone();
+    let defined: Vec<String> = input
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let mut name = match self_ty.as_ref() {
+        syn::Type::Path(type_path) => type_path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    let mut methods: Vec<TokenStream2> = Vec::new();
+
+    for arg in args {
+        if arg.path().is_ident("encrypted") && matches!(arg, Meta::Path(_)) {
+            if defined.iter().any(|m| m == "encrypted") {
+                return Err(syn::Error::new_spanned(&arg, "`encrypted` is set here and also implemented on the job"));
+            }
+            methods.push(quote! {
+                fn encrypted(&self) -> bool {
+                    true
+                }
+            });
+            continue;
+        }
+        let Meta::NameValue(arg) = arg else {
+            return Err(syn::Error::new_spanned(arg, "expected `key = value` or `encrypted`"));
+        };
+        let key = arg.path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+        let value = &arg.value;
+        if defined.contains(&key) {
+            return Err(syn::Error::new_spanned(&arg.path, format!("`{}` is set here and also implemented on the job", key)));
+        }
+
+        match key.as_str() {
+            "name" => match value {
+                Expr::Lit(syn::ExprLit { lit: Lit::Str(lit), .. }) => name = Some(lit.value()),
+                _ => return Err(syn::Error::new_spanned(value, "expected a string literal")),
+            },
+            "queue" => methods.push(quote! {
+                fn queue(&self) -> Option<String> {
+                    Some(#value.to_string())
+                }
+            }),
+            "tries" => methods.push(quote! {
+                fn tries(&self) -> Option<u32> {
+                    Some(#value)
+                }
+            }),
+            "delay" => methods.push(quote! {
+                fn delay(&self) -> Option<u64> {
+                    Some(#value)
+                }
+            }),
+            "backoff" => methods.push(quote! {
+                fn backoff(&self) -> Option<crate::framework::queue::Backoff> {
+                    Some(crate::framework::queue::Backoff::from(#value))
+                }
+            }),
+            "timeout" => methods.push(quote! {
+                fn timeout(&self) -> Option<u64> {
+                    Some(#value)
+                }
+            }),
+            _ => return Err(syn::Error::new_spanned(&arg.path, format!("unknown job option `{}`", key))),
+        }
+    }
+
+    if !defined.iter().any(|m| m == "serialize") {
+        methods.push(quote! {
+            fn serialize(&self) -> Result<String, Box<dyn std::error::Error>> {
+                Ok(serde_json::to_string(self)?)
+            }
+        });
+    }
+    if !defined.iter().any(|m| m == "deserialize") {
+        methods.push(quote! {
+            fn deserialize(data: &str) -> Result<Box<dyn crate::framework::queue::Job>, Box<dyn std::error::Error>> {
+                let job: #self_ty = serde_json::from_str(data)?;
+                Ok(Box::new(job))
+            }
+        });
+    }
+    if !defined.iter().any(|m| m == "type_name") {
+        let name = name.ok_or_else(|| syn::Error::new_spanned(&self_ty, "set the job's `name`"))?;
+        methods.push(quote! {
+            fn type_name() -> String {
+                #name.to_string()
+            }
+        });
+    }
+
+    for method in methods {
+        input.items.push(syn::parse2(method)?);
+    }
+
+    Ok(quote! {
+        #input
+
+        #[doc(hidden)]
+        const _: () = {
+            inventory::submit!(crate::framework::queue::worker::JobRegistration {
+                job_type: <#self_ty as crate::framework::queue::Job>::type_name,
+                deserialize: <#self_ty as crate::framework::queue::Job>::deserialize,
+            });
+        };
+    })
+}
@@ -6,6 +6,9 @@ mod m20250222_022856_create_posts_table;
 mod m20250222_030920_create_comments_table;
 mod m20250222_093246_add_auth_fields_to_users;
 mod m20250222_215612_create_jobs_table;
+mod m20250223_101500_create_failed_jobs_table;
+mod m20250224_090000_create_job_batches_table;
+mod m20250225_090000_create_cache_locks_table;
 
 pub struct Migrator;
 
@@ -19,6 +22,9 @@ impl MigratorTrait for Migrator {
             Box::new(m20250222_030920_create_comments_table::Migration),
             Box::new(m20250222_093246_add_auth_fields_to_users::Migration),
             Box::new(m20250222_215612_create_jobs_table::Migration),
+            Box::new(m20250223_101500_create_failed_jobs_table::Migration),
+            Box::new(m20250224_090000_create_job_batches_table::Migration),
+            Box::new(m20250225_090000_create_cache_locks_table::Migration),
         ]
     }
 }
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(FailedJobs::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(FailedJobs::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(FailedJobs::Queue).string().not_null())
+                    .col(ColumnDef::new(FailedJobs::Payload).text().not_null())
+                    .col(ColumnDef::new(FailedJobs::Exception).text().not_null())
+                    .col(ColumnDef::new(FailedJobs::FailedAt).timestamp_with_time_zone().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Add index for listing failures newest first
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_failed_jobs_failed_at")
+                    .table(FailedJobs::Table)
+                    .col(FailedJobs::FailedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FailedJobs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum FailedJobs {
+    Table,
+    Id,
+    Queue,
+    Payload,
+    Exception,
+    FailedAt,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobBatches::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(JobBatches::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(JobBatches::Name).string().not_null())
+                    .col(ColumnDef::new(JobBatches::TotalJobs).integer().not_null())
+                    .col(ColumnDef::new(JobBatches::PendingJobs).integer().not_null())
+                    .col(ColumnDef::new(JobBatches::FailedJobs).integer().not_null())
+                    .col(ColumnDef::new(JobBatches::SkippedJobs).integer().not_null().default(0))
+                    .col(ColumnDef::new(JobBatches::FailedJobIds).text().not_null())
+                    .col(ColumnDef::new(JobBatches::Options).text().not_null())
+                    .col(ColumnDef::new(JobBatches::CancelledAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(JobBatches::CreatedAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(JobBatches::FinishedAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(JobBatches::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum JobBatches {
+    Table,
+    Id,
+    Name,
+    TotalJobs,
+    PendingJobs,
+    FailedJobs,
+    SkippedJobs,
+    FailedJobIds,
+    Options,
+    CancelledAt,
+    CreatedAt,
+    FinishedAt,
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CacheLocks::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(CacheLocks::Key).string().not_null().primary_key())
+                    .col(ColumnDef::new(CacheLocks::Owner).string().not_null())
+                    .col(ColumnDef::new(CacheLocks::Expiration).big_integer())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CacheLocks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CacheLocks {
+    Table,
+    Key,
+    Owner,
+    Expiration,
+}
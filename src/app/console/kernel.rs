@@ -1,3 +1,4 @@
+use crate::framework::cache::Cache;
 use crate::framework::schedule::scheduler;
 use chrono::NaiveTime;
 
@@ -77,6 +78,22 @@ pub async fn schedule() {
         Err(e) => eprintln!("Failed to create custom task: {:?}", e),
     }
 
+    // Delete expired cache items, which the database store otherwise keeps until they're read
+    match sched
+        .task("cache-prune", || {
+            if !Cache::is_initialized() {
+                return;
+            }
+            tokio::spawn(async {
+                let pruned = Cache::prune().await;
+                println!("Pruned {} expired cache item(s)", pruned);
+            });
+        })
+        .hourly() {
+        Ok(task) => sched.add_task(task).await,
+        Err(e) => eprintln!("Failed to create cache prune task: {:?}", e),
+    }
+
     println!("Finished initializing scheduler tasks");
     
     // Run the scheduler
@@ -1,9 +1,3 @@
 mod test_job;
 
-pub use test_job::TestJob;
-
-use crate::framework::queue::worker::register_job;
-
-pub fn register_jobs() {
-    register_job::<TestJob>();
-} 
\ No newline at end of file
+pub use test_job::TestJob;
\ No newline at end of file
@@ -2,6 +2,11 @@ use clap::{Parser, Subcommand};
 use std::error::Error;
 use std::time::Duration;
 use ruskit::framework::run;
+use ruskit::framework::cache::Cache;
+use ruskit::framework::crypt::Encrypter;
+use ruskit::framework::queue::Queue;
+use ruskit::framework::queue::dashboard::DashboardStats;
+use uuid::Uuid;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,10 +21,16 @@ enum Commands {
     Serve,
     /// Run the scheduler
     Schedule,
+    /// Delete expired items from the cache store
+    #[command(name = "cache:prune")]
+    CachePrune,
+    /// Generate a new application encryption key for APP_KEY
+    #[command(name = "key:generate")]
+    KeyGenerate,
     /// Run the queue worker
     #[command(name = "queue:work")]
     QueueWork {
-        /// The queue to process
+        /// The queue to process, or comma-separated queues in priority order
         #[arg(short, long, default_value = "default")]
         queue: String,
         /// Sleep duration in seconds between polling for new jobs
@@ -28,6 +39,49 @@ enum Commands {
         /// Maximum number of times to attempt a job
         #[arg(short, long, default_value = "3")]
         tries: u32,
+        /// Seconds to wait before retrying a failed job
+        #[arg(long, default_value = "30")]
+        backoff: u64,
+        /// Seconds a job may run before it is cancelled, unlimited by default
+        #[arg(long)]
+        timeout: Option<u64>,
+        /// Number of jobs to process at the same time
+        #[arg(long, default_value = "1")]
+        concurrency: usize,
+        /// Stop after processing this many jobs
+        #[arg(long)]
+        max_jobs: Option<u64>,
+        /// Stop after running for this many seconds
+        #[arg(long)]
+        max_time: Option<u64>,
+        /// Stop once the queues are empty
+        #[arg(long)]
+        stop_when_empty: bool,
+    },
+    /// List all failed queue jobs
+    #[command(name = "queue:failed")]
+    QueueFailed,
+    /// Retry a failed queue job
+    #[command(name = "queue:retry")]
+    QueueRetry {
+        /// The ID of the failed job, or "all" to retry every failed job
+        id: String,
+    },
+    /// Delete a failed queue job
+    #[command(name = "queue:forget")]
+    QueueForget {
+        /// The ID of the failed job
+        id: Uuid,
+    },
+    /// Delete all failed queue jobs
+    #[command(name = "queue:flush")]
+    QueueFlush,
+    /// Show the state of the queues and recent failures
+    #[command(name = "queue:monitor")]
+    QueueMonitor {
+        /// Comma-separated queues to report on
+        #[arg(short, long, default_value = "default")]
+        queue: String,
     },
 }
 
@@ -40,28 +94,144 @@ async fn main() -> Result<(), Box<dyn Error>> {
             run().await?;
         }
         Commands::Schedule => {
+            // Scheduled tasks such as the cache sweep need the application services
+            bootstrap().await?;
             ruskit::app::console::kernel::schedule().await;
         }
-        Commands::QueueWork { queue, sleep, tries } => {
+        Commands::CachePrune => {
+            bootstrap().await?;
+
+            let pruned = Cache::prune().await;
+            println!("Pruned {} expired cache item(s)", pruned);
+        }
+        Commands::KeyGenerate => {
+            println!("{}", Encrypter::generate_key());
+        }
+        Commands::QueueWork { queue, sleep, tries, backoff, timeout, concurrency, max_jobs, max_time, stop_when_empty } => {
             // Initialize the application
-            ruskit::framework::bootstrap::app::bootstrap().await
-                .map_err(|e| format!("Failed to bootstrap application: {}", e))?;
+            bootstrap().await?;
 
             // Create and run the worker
-            let worker = ruskit::framework::queue::Queue::worker(&queue)
+            let mut worker = Queue::worker(&queue)
                 .sleep_duration(Duration::from_secs(sleep))
-                .max_tries(tries);
+                .max_tries(tries)
+                .backoff(backoff)
+                .concurrency(concurrency);
+            if let Some(timeout) = timeout {
+                worker = worker.timeout(Duration::from_secs(timeout));
+            }
+            if let Some(max_jobs) = max_jobs {
+                worker = worker.max_jobs(max_jobs);
+            }
+            if let Some(max_time) = max_time {
+                worker = worker.max_time(Duration::from_secs(max_time));
+            }
+            if stop_when_empty {
+                worker = worker.stop_when_empty();
+            }
+
+            // On SIGTERM or Ctrl+C, jobs in progress finish before the worker exits
+            worker.run_until_signal().await;
+        }
+        Commands::QueueFailed => {
+            bootstrap().await?;
+
+            let failed = Queue::failed_jobs().await?;
+            if failed.is_empty() {
+                println!("No failed jobs!");
+                return Ok(());
+            }
+
+            println!("{:<36}  {:<12}  {:<20}  {:<19}  Exception", "ID", "Queue", "Job", "Failed At");
+            for job in failed {
+                let job_type = serde_json::from_str::<serde_json::Value>(&job.payload)
+                    .ok()
+                    .and_then(|payload| payload["job_type"].as_str().map(String::from))
+                    .unwrap_or_else(|| "Unknown".to_string());
+                let exception = job.exception.lines().next().unwrap_or_default();
+                println!(
+                    "{:<36}  {:<12}  {:<20}  {:<19}  {}",
+                    job.id,
+                    job.queue,
+                    job_type,
+                    job.failed_at.format("%Y-%m-%d %H:%M:%S"),
+                    exception,
+                );
+            }
+        }
+        Commands::QueueRetry { id } => {
+            bootstrap().await?;
+
+            if id == "all" {
+                let retried = Queue::retry_all().await?;
+                println!("Pushed {} failed job(s) back onto the queue", retried.len());
+            } else {
+                let id = Uuid::parse_str(&id).map_err(|e| format!("Invalid job ID {}: {}", id, e))?;
+                Queue::retry(id).await?;
+                println!("The failed job [{}] has been pushed back onto the queue", id);
+            }
+        }
+        Commands::QueueForget { id } => {
+            bootstrap().await?;
+
+            if Queue::forget_failed(id).await? {
+                println!("Failed job [{}] deleted successfully", id);
+            } else {
+                println!("No failed job matches the given ID [{}]", id);
+            }
+        }
+        Commands::QueueFlush => {
+            bootstrap().await?;
+
+            let deleted = Queue::flush_failed().await?;
+            println!("Deleted {} failed job(s)", deleted);
+        }
+        Commands::QueueMonitor { queue } => {
+            bootstrap().await?;
+
+            let queues: Vec<String> = queue.split(',').map(|q| q.trim().to_string()).filter(|q| !q.is_empty()).collect();
+            let stats = DashboardStats::collect(&queues).await?;
 
-            // Handle Ctrl+C gracefully
-            let worker_running = worker.running.clone();
-            ctrlc::set_handler(move || {
-                println!("\nReceived Ctrl+C, shutting down worker...");
-                worker_running.store(false, std::sync::atomic::Ordering::SeqCst);
-            })?;
+            println!(
+                "{:<16}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>13}  {:>10}",
+                "Queue", "Pending", "Reserved", "Delayed", "Failed", "Oldest", "Processed/min", "Failed/min"
+            );
+            for q in &stats.queues {
+                println!(
+                    "{:<16}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>13.1}  {:>10.1}",
+                    q.queue,
+                    q.pending,
+                    q.reserved,
+                    q.delayed,
+                    q.failed,
+                    q.oldest_pending_seconds.map(|s| format!("{}s", s)).unwrap_or_else(|| "-".to_string()),
+                    q.processed_per_minute,
+                    q.failed_per_minute,
+                );
+            }
 
-            worker.run().await;
+            if !stats.recent_failures.is_empty() {
+                println!();
+                println!("Recent failures:");
+                for job in &stats.recent_failures {
+                    println!(
+                        "{}  {:<12}  {}  {}",
+                        job.id,
+                        job.queue,
+                        job.failed_at.format("%Y-%m-%d %H:%M:%S"),
+                        job.exception.lines().next().unwrap_or_default(),
+                    );
+                }
+            }
         }
     }
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Initialize the application so the queue driver is available
+async fn bootstrap() -> Result<(), Box<dyn Error>> {
+    ruskit::framework::bootstrap::app::bootstrap_services().await
+        .map_err(|e| format!("Failed to bootstrap application: {}", e))?;
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use ruskit::app::jobs::TestJob;
 use ruskit::framework::queue::Queue;
-use ruskit::framework::bootstrap::app::bootstrap;
+use ruskit::framework::bootstrap::app::bootstrap_services;
 use dotenvy::dotenv;
 
 #[tokio::main]
@@ -9,7 +9,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
 
     // Initialize the application
-    bootstrap().await
+    bootstrap_services().await
         .map_err(|e| format!("Failed to bootstrap application: {}", e))?;
 
     // Create and dispatch a test job
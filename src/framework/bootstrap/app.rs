@@ -105,43 +105,71 @@ impl Application {
 pub async fn bootstrap() -> Result<DatabaseConnection, String> {
     println!("Starting bootstrap process...");
     
+    let db = connect_database().await?;
+    let (cache_config, queue_config) = load_configs()?;
+    init_services(cache_config, queue_config, Some(db.clone())).await?;
+
+    Ok(db)
+}
+
+/// Initialize the application for commands that don't serve requests, such as the queue
+/// worker. The database is only connected when the cache or queue driver needs it.
+pub async fn bootstrap_services() -> Result<(), String> {
+    println!("Starting bootstrap process...");
+
+    let (cache_config, queue_config) = load_configs()?;
+    let db = if cache_config.needs_database() || queue_config.needs_database() {
+        Some(connect_database().await?)
+    } else {
+        println!("Skipping database connection, no configured driver needs it");
+        None
+    };
+    init_services(cache_config, queue_config, db).await
+}
+
+async fn connect_database() -> Result<DatabaseConnection, String> {
     // Initialize the database connection
     println!("Initializing database connection...");
     let db = crate::framework::database::init()
         .await
         .map_err(|e| format!("Failed to initialize database: {}", e))?;
     println!("Database connection initialized successfully");
+    Ok((*db).clone())
+}
 
+fn load_configs() -> Result<(CacheConfig, QueueConfig), String> {
     // Load the cache configuration
     println!("Loading cache configuration...");
-    let cache_config = CacheConfig::default();
+    let cache_config = CacheConfig::load()
+        .map_err(|e| format!("Failed to load cache configuration: {}", e))?;
     println!("Cache configuration loaded successfully");
 
-    // Initialize the cache with the database connection
-    println!("Initializing cache...");
-    init_cache(cache_config, (*db).clone()).await
-        .map_err(|e| format!("Failed to initialize cache: {}", e))?;
-    println!("Cache initialized successfully");
-
     // Load the queue configuration
     println!("Loading queue configuration...");
     let queue_config = QueueConfig::default();
     println!("Queue configuration loaded successfully");
 
+    Ok((cache_config, queue_config))
+}
+
+/// Initialize the cache, queue and storage, handing the database connection to the drivers that use it
+async fn init_services(cache_config: CacheConfig, queue_config: QueueConfig, db: Option<DatabaseConnection>) -> Result<(), String> {
+    // Initialize the cache with the database connection
+    println!("Initializing cache...");
+    init_cache(cache_config, db.clone()).await
+        .map_err(|e| format!("Failed to initialize cache: {}", e))?;
+    println!("Cache initialized successfully");
+
     // Initialize the queue with the database connection
     println!("Initializing queue...");
-    init_queue(queue_config, (*db).clone()).await
+    init_queue(queue_config, db).await
         .map_err(|e| format!("Failed to initialize queue: {}", e))?;
     println!("Queue initialized successfully");
 
-    // Register jobs
-    println!("Registering jobs...");
-    crate::app::jobs::register_jobs();
-    println!("Jobs registered successfully");
-
     // Load the storage configuration
     println!("Loading storage configuration...");
-    let storage_config = StorageConfig::default();
+    let storage_config = StorageConfig::load()
+        .map_err(|e| format!("Failed to load storage configuration: {}", e))?;
     println!("Storage configuration loaded successfully");
 
     // Initialize the storage system
@@ -150,7 +178,7 @@ pub async fn bootstrap() -> Result<DatabaseConnection, String> {
         .map_err(|e| format!("Failed to initialize storage: {}", e))?;
     println!("Storage system initialized successfully");
 
-    Ok((*db).clone())
+    Ok(())
 }
 
 /// Get the application's middleware stack
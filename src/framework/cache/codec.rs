@@ -0,0 +1,130 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+
+/// Leading bytes marking values that aren't plain JSON. Plain JSON never starts with them,
+/// so values written before a store changed format can still be read.
+const MESSAGE_PACK: u8 = 0x00;
+const MESSAGE_PACK_ZSTD: u8 = 0x01;
+const JSON_ZSTD: u8 = 0x02;
+
+/// Marks binary values stored in a text column
+const TEXT_PREFIX: &str = "base64:";
+
+/// The format values are serialized to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Serializer {
+    #[default]
+    Json,
+    /// More compact than JSON and faster to parse, but not human readable
+    MessagePack,
+}
+
+impl Serializer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "msgpack" | "messagepack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+}
+
+/// Turns cache values into the bytes kept by stores outside the process, optionally
+/// compressing large values with zstd.
+///
+/// Integers are always written as plain text so stores can increment them in place.
+#[derive(Debug, Clone, Default)]
+pub struct Codec {
+    serializer: Serializer,
+    compress_threshold: Option<usize>,
+}
+
+impl Codec {
+    pub fn new(serializer: Serializer) -> Self {
+        Self {
+            serializer,
+            compress_threshold: None,
+        }
+    }
+
+    /// Compress serialized values larger than `bytes`
+    pub fn compress_above(mut self, bytes: usize) -> Self {
+        self.compress_threshold = Some(bytes);
+        self
+    }
+
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, String> {
+        if value.is_i64() || value.is_u64() {
+            return Ok(value.to_string().into_bytes());
+        }
+
+        let (serialized, marker, compressed_marker) = match self.serializer {
+            Serializer::Json => (serde_json::to_vec(value).map_err(|e| e.to_string())?, None, JSON_ZSTD),
+            Serializer::MessagePack => (
+                rmp_serde::to_vec(value).map_err(|e| e.to_string())?,
+                Some(MESSAGE_PACK),
+                MESSAGE_PACK_ZSTD,
+            ),
+        };
+
+        if self.compress_threshold.is_some_and(|threshold| serialized.len() > threshold) {
+            let compressed = zstd::encode_all(serialized.as_slice(), 0).map_err(|e| e.to_string())?;
+            return Ok([&[compressed_marker][..], &compressed].concat());
+        }
+        Ok(match marker {
+            Some(marker) => [&[marker][..], &serialized].concat(),
+            None => serialized,
+        })
+    }
+
+    /// Decode a value written by `encode`, whatever serializer and compression it used
+    pub fn decode(&self, bytes: &[u8]) -> Option<Value> {
+        let (marker, rest) = bytes.split_first()?;
+        match *marker {
+            MESSAGE_PACK => rmp_serde::from_slice(rest).ok(),
+            MESSAGE_PACK_ZSTD => rmp_serde::from_slice(&zstd::decode_all(rest).ok()?).ok(),
+            JSON_ZSTD => serde_json::from_slice(&zstd::decode_all(rest).ok()?).ok(),
+            _ => serde_json::from_slice(bytes).ok(),
+        }
+    }
+
+    /// Encode a value for a text column, base64 encoding it if it isn't plain JSON
+    pub fn encode_text(&self, value: &Value) -> Result<String, String> {
+        let bytes = self.encode(value)?;
+        Ok(String::from_utf8(bytes)
+            .unwrap_or_else(|e| format!("{}{}", TEXT_PREFIX, STANDARD.encode(e.into_bytes()))))
+    }
+
+    pub fn decode_text(&self, text: &str) -> Option<Value> {
+        match text.strip_prefix(TEXT_PREFIX) {
+            Some(encoded) => self.decode(&STANDARD.decode(encoded).ok()?),
+            None => self.decode(text.as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trips_every_format() {
+        let report = json!({ "rows": vec!["quarterly revenue"; 200], "total": 1.5 });
+        let json = Codec::default();
+        let msgpack = Codec::new(Serializer::MessagePack);
+        let compressed = Codec::new(Serializer::MessagePack).compress_above(1024);
+
+        for codec in [&json, &msgpack, &compressed] {
+            let encoded = codec.encode(&report).unwrap();
+            assert_eq!(json.decode(&encoded), Some(report.clone()));
+            assert_eq!(json.decode_text(&codec.encode_text(&report).unwrap()), Some(report.clone()));
+            assert_eq!(codec.encode(&json!(42)).unwrap(), b"42");
+        }
+
+        assert_eq!(json.encode(&report).unwrap(), serde_json::to_vec(&report).unwrap());
+        assert!(compressed.encode(&report).unwrap().len() < msgpack.encode(&report).unwrap().len());
+        assert_eq!(msgpack.decode(br#"{"legacy":true}"#), Some(json!({ "legacy": true })));
+    }
+}
@@ -1,7 +1,12 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
 use std::time::Duration;
 use sea_orm::DatabaseConnection;
-use crate::framework::cache::{CacheStore, CACHE_STORE};
-use crate::framework::cache::drivers::{DatabaseStore, RedisStore};
+use serde::Deserialize;
+use crate::framework::cache::{CacheStore, Repository, CACHE_STORE, NAMED_STORES};
+use crate::framework::cache::codec::{Codec, Serializer};
+use crate::framework::cache::drivers::{DatabaseStore, MemoryStore, RedisStore, TieredStore};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -9,6 +14,19 @@ use tokio::sync::RwLock;
 pub enum CacheDriver {
     Database,
     Redis,
+    /// An in-process store, handy for tests and single-node deployments
+    Memory,
+}
+
+impl CacheDriver {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "database" => Some(Self::Database),
+            "redis" => Some(Self::Redis),
+            "memory" => Some(Self::Memory),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -16,33 +34,246 @@ pub struct CacheConfig {
     pub driver: CacheDriver,
     pub redis_url: Option<String>,
     pub default_ttl: Option<Duration>,
+    /// Maximum number of items the memory store holds before evicting the least recently used
+    pub memory_max_entries: Option<usize>,
+    /// Maximum approximate size in bytes of the items in the memory store
+    pub memory_max_bytes: Option<usize>,
+    /// Keep items read from the database or Redis in process memory for this long, saving
+    /// a round trip for hot keys. Other processes may see stale values for up to this long.
+    pub local_ttl: Option<Duration>,
+    /// Prepended to every key in the database and Redis stores, so apps and environments
+    /// sharing a database or Redis instance keep their items apart
+    pub prefix: String,
+    /// The format values are written in by the database and Redis stores
+    pub serializer: Serializer,
+    /// Compress serialized values larger than this many bytes with zstd
+    pub compress_threshold: Option<usize>,
+    /// Additional stores reachable through `Cache::store_named`, keyed by name
+    pub stores: HashMap<String, CacheConfig>,
 }
 
 impl Default for CacheConfig {
     fn default() -> Self {
         Self {
-            driver: CacheDriver::Database,
-            redis_url: None,
+            driver: default_driver(),
+            redis_url: env::var("REDIS_URL").ok(),
             default_ttl: Some(Duration::from_secs(3600)), // 1 hour
+            memory_max_entries: Some(10_000),
+            memory_max_bytes: None,
+            local_ttl: None,
+            prefix: env::var("CACHE_PREFIX").unwrap_or_else(|_| "ruskit_cache:".to_string()),
+            serializer: Serializer::Json,
+            compress_threshold: None,
+            stores: HashMap::new(),
         }
     }
 }
 
-pub async fn init_cache(config: CacheConfig, db: DatabaseConnection) -> Result<(), String> {
-    // If cache store is already initialized, return early
-    if CACHE_STORE.get().is_some() {
-        return Ok(());
+impl CacheConfig {
+    /// Whether this store or any of the named stores keeps its items in the database
+    pub fn needs_database(&self) -> bool {
+        matches!(self.driver, CacheDriver::Database) || self.stores.values().any(CacheConfig::needs_database)
+    }
+}
+
+fn default_driver() -> CacheDriver {
+    env::var("CACHE_DRIVER")
+        .ok()
+        .and_then(|name| CacheDriver::from_name(&name))
+        .unwrap_or(CacheDriver::Database)
+}
+
+/// A store as written in `config/cache.toml`, with durations in seconds
+#[derive(Debug, Deserialize)]
+struct StoreFile {
+    driver: Option<String>,
+    redis_url: Option<String>,
+    default_ttl: Option<u64>,
+    memory_max_entries: Option<usize>,
+    memory_max_bytes: Option<usize>,
+    local_ttl: Option<u64>,
+    prefix: Option<String>,
+    serializer: Option<String>,
+    compress_threshold: Option<usize>,
+    #[serde(default)]
+    stores: HashMap<String, StoreFile>,
+}
+
+impl StoreFile {
+    /// Apply the settings from the file over the defaults
+    fn into_config(self) -> Result<CacheConfig, String> {
+        let mut config = CacheConfig::default();
+        if let Some(driver) = self.driver {
+            config.driver = CacheDriver::from_name(&driver)
+                .ok_or_else(|| format!("Unsupported cache driver: {}", driver))?;
+        }
+        if self.redis_url.is_some() {
+            config.redis_url = self.redis_url;
+        }
+        if let Some(default_ttl) = self.default_ttl {
+            config.default_ttl = Some(Duration::from_secs(default_ttl));
+        }
+        if self.memory_max_entries.is_some() {
+            config.memory_max_entries = self.memory_max_entries;
+        }
+        config.memory_max_bytes = self.memory_max_bytes;
+        config.local_ttl = self.local_ttl.map(Duration::from_secs);
+        if let Some(prefix) = self.prefix {
+            config.prefix = prefix;
+        }
+        if let Some(serializer) = self.serializer {
+            config.serializer = Serializer::from_name(&serializer)
+                .ok_or_else(|| format!("Unsupported cache serializer: {}", serializer))?;
+        }
+        config.compress_threshold = self.compress_threshold;
+        config.stores = self
+            .stores
+            .into_iter()
+            .map(|(name, store)| Ok((name, store.into_config()?)))
+            .collect::<Result<_, String>>()?;
+        Ok(config)
     }
+}
 
-    let store: Box<dyn CacheStore + Send + Sync> = match config.driver {
-        CacheDriver::Database => Box::new(DatabaseStore::new(db)),
+impl CacheConfig {
+    /// Load `config/cache.toml`, falling back to the environment when the file doesn't exist
+    pub fn load() -> Result<Self, String> {
+        let path = Path::new("config/cache.toml");
+        if path.exists() {
+            Self::from_file(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Load the cache configuration from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&contents)
+    }
+
+    /// Parse the cache configuration from TOML. Settings left out take their defaults.
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let file: StoreFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+        file.into_config()
+    }
+}
+
+/// Build the store described by a configuration, ignoring its named stores
+fn build_store(config: &CacheConfig, db: Option<&DatabaseConnection>) -> Result<Box<dyn CacheStore + Send + Sync>, String> {
+    let memory_store = || {
+        let mut store = MemoryStore::new();
+        if let Some(max_entries) = config.memory_max_entries {
+            store = store.max_entries(max_entries);
+        }
+        if let Some(max_bytes) = config.memory_max_bytes {
+            store = store.max_bytes(max_bytes);
+        }
+        store
+    };
+
+    let mut codec = Codec::new(config.serializer);
+    if let Some(threshold) = config.compress_threshold {
+        codec = codec.compress_above(threshold);
+    }
+
+    let mut store: Box<dyn CacheStore + Send + Sync> = match config.driver {
+        CacheDriver::Database => {
+            let db = db.ok_or("The database cache driver requires a database connection")?;
+            Box::new(DatabaseStore::new(db.clone()).prefix(&config.prefix).codec(codec))
+        }
         CacheDriver::Redis => {
-            let redis_url = config.redis_url.ok_or("Redis URL not configured")?;
-            Box::new(RedisStore::new(&redis_url).map_err(|e| e.to_string())?)
+            let redis_url = config.redis_url.as_ref().ok_or("Redis URL not configured")?;
+            Box::new(
+                RedisStore::new(redis_url)
+                    .map_err(|e| e.to_string())?
+                    .prefix(&config.prefix)
+                    .codec(codec),
+            )
         }
+        CacheDriver::Memory => Box::new(memory_store()),
     };
 
+    // The memory store gains nothing from a second memory tier in front of it
+    if let Some(local_ttl) = config.local_ttl.filter(|_| !matches!(config.driver, CacheDriver::Memory)) {
+        store = Box::new(TieredStore::new(memory_store(), store).local_ttl(local_ttl));
+    }
+    Ok(store)
+}
+
+/// Initialize the default cache store and the named stores. A database connection is only
+/// required by the database driver.
+pub async fn init_cache(config: CacheConfig, db: Option<DatabaseConnection>) -> Result<(), String> {
+    // If cache store is already initialized, return early
+    if CACHE_STORE.get().is_some() {
+        return Ok(());
+    }
+
+    let store = build_store(&config, db.as_ref())?;
+    let mut named = HashMap::new();
+    for (name, store_config) in &config.stores {
+        let store = build_store(store_config, db.as_ref())
+            .map_err(|e| format!("Failed to create cache store [{}]: {}", name, e))?;
+        named.insert(name.clone(), Repository::new(Arc::new(RwLock::new(store))));
+    }
+
+    NAMED_STORES
+        .set(named)
+        .map_err(|_| "Failed to initialize named cache stores".to_string())?;
     CACHE_STORE
         .set(Arc::new(RwLock::new(store)))
         .map_err(|_| "Failed to initialize cache store".to_string())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_named_stores() {
+        let config = CacheConfig::from_toml(
+            r#"
+            driver = "memory"
+
+            [stores.sessions]
+            driver = "redis"
+            redis_url = "redis://127.0.0.1:6379"
+            local_ttl = 5
+
+            [stores.memo]
+            driver = "memory"
+            memory_max_entries = 100
+
+            [stores.reports]
+            prefix = "reports:"
+            serializer = "msgpack"
+            compress_threshold = 65536
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(config.driver, CacheDriver::Memory));
+        let sessions = &config.stores["sessions"];
+        assert!(matches!(sessions.driver, CacheDriver::Redis));
+        assert_eq!(sessions.redis_url.as_deref(), Some("redis://127.0.0.1:6379"));
+        assert_eq!(sessions.local_ttl, Some(Duration::from_secs(5)));
+        assert_eq!(config.stores["memo"].memory_max_entries, Some(100));
+        let reports = &config.stores["reports"];
+        assert_eq!(reports.prefix, "reports:");
+        assert_eq!(reports.serializer, Serializer::MessagePack);
+        assert_eq!(reports.compress_threshold, Some(65536));
+
+        assert!(CacheConfig::from_toml("driver = \"memcached\"").is_err());
+    }
+
+    #[test]
+    fn test_needs_database_when_any_store_uses_it() {
+        let memory = "driver = \"memory\"\n[stores.memo]\ndriver = \"memory\"\n";
+        assert!(!CacheConfig::from_toml(memory).unwrap().needs_database());
+
+        let config = CacheConfig::from_toml(&format!("{}[stores.reports]\ndriver = \"database\"\n", memory)).unwrap();
+        assert!(config.needs_database());
+    }
+}
@@ -1,6 +1,8 @@
+use crate::framework::cache::codec::Codec;
 use crate::framework::cache::CacheStore;
 use async_trait::async_trait;
-use sea_orm::{DatabaseConnection, EntityTrait, Set, ActiveModelTrait, QueryFilter, ColumnTrait};
+use sea_orm::{DatabaseConnection, EntityTrait, Set, ActiveModelTrait, QueryFilter, ColumnTrait, DbBackend, TransactionTrait};
+use sea_orm::sea_query::{Expr, LikeExpr, OnConflict};
 use serde_json::Value;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use sea_orm::entity::prelude::*;
@@ -8,7 +10,7 @@ use sea_orm::entity::prelude::*;
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "cache")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub key: String,
     #[sea_orm(column_type = "Text")]
     pub value: String,
@@ -20,13 +22,54 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// Locks are kept in their own table so they can't be overwritten by cache writes
+pub mod cache_lock {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "cache_locks")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub key: String,
+        pub owner: String,
+        pub expiration: Option<i64>,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
 pub struct DatabaseStore {
     db: DatabaseConnection,
+    prefix: String,
+    codec: Codec,
 }
 
 impl DatabaseStore {
     pub fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+        Self {
+            db,
+            prefix: String::new(),
+            codec: Codec::default(),
+        }
+    }
+
+    /// Prefix every key, so apps sharing the cache table don't see or flush each other's items
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// How values are serialized and compressed
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
     }
 
     fn get_expiration(ttl: Option<Duration>) -> Option<i64> {
@@ -39,53 +82,119 @@ impl DatabaseStore {
                 .as_secs() as i64
         })
     }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    /// Add to a counter in a single upsert, so concurrent increments can't overwrite each
+    /// other. A missing or expired counter starts from zero and no longer expires.
+    async fn add_to_counter(&self, key: &str, value: i64) -> Result<i64, sea_orm::DbErr> {
+        let (integer, text) = match self.db.get_database_backend() {
+            DbBackend::MySql => ("SIGNED", "CHAR"),
+            DbBackend::Postgres => ("BIGINT", "TEXT"),
+            DbBackend::Sqlite => ("INTEGER", "TEXT"),
+        };
+        let now = Self::now();
+
+        // MySQL applies the assignments in order, so the value is set before the expiration
+        // it checks is cleared
+        let on_conflict = OnConflict::column(Column::Key)
+            .value(
+                Column::Value,
+                Expr::cust_with_values(
+                    format!(
+                        "CASE WHEN cache.expiration IS NOT NULL AND cache.expiration < ? THEN ? \
+                         ELSE CAST(CAST(cache.value AS {}) + ? AS {}) END",
+                        integer, text
+                    ),
+                    [sea_orm::Value::from(now), sea_orm::Value::from(value.to_string()), sea_orm::Value::from(value)],
+                ),
+            )
+            .value(
+                Column::Expiration,
+                Expr::cust_with_values(
+                    "CASE WHEN cache.expiration IS NOT NULL AND cache.expiration < ? THEN NULL \
+                     ELSE cache.expiration END",
+                    [now],
+                ),
+            )
+            .to_owned();
+
+        let key = self.key(key);
+        let txn = self.db.begin().await?;
+        Entity::insert(ActiveModel {
+            key: Set(key.to_string()),
+            value: Set(value.to_string()),
+            expiration: Set(None),
+        })
+        .on_conflict(on_conflict)
+        .exec(&txn)
+        .await?;
+
+        // The upsert holds the row until the transaction commits, so this reads our own write
+        let counter = Entity::find_by_id(key)
+            .one(&txn)
+            .await?
+            .and_then(|cache| cache.value.parse().ok())
+            .unwrap_or_default();
+        txn.commit().await?;
+        Ok(counter)
+    }
 }
 
 #[async_trait]
 impl CacheStore for DatabaseStore {
     async fn get(&self, key: &str) -> Option<Value> {
+        self.get_with_ttl(key).await.map(|(value, _)| value)
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
         let cache = Entity::find()
-            .filter(Column::Key.eq(key))
+            .filter(Column::Key.eq(self.key(key)))
             .one(&self.db)
             .await
-            .ok()?;
-
-        if let Some(cache) = cache {
-            if let Some(expiration) = cache.expiration {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64;
-                if now > expiration {
-                    self.forget(key).await;
-                    return None;
-                }
-            }
-            serde_json::from_str(&cache.value).ok()
-        } else {
-            None
+            .ok()??;
+
+        let now = Self::now();
+        if cache.expiration.is_some_and(|expiration| now > expiration) {
+            self.forget(key).await;
+            return None;
         }
+        let ttl = cache.expiration.map(|expiration| Duration::from_secs((expiration - now) as u64));
+        Some((self.codec.decode_text(&cache.value)?, ttl))
     }
 
     async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
-        let value = match serde_json::to_string(&value) {
+        let value = match self.codec.encode_text(&value) {
             Ok(v) => v,
             Err(_) => return false,
         };
         let expiration = Self::get_expiration(ttl);
 
         let cache = ActiveModel {
-            key: Set(key.to_string()),
+            key: Set(self.key(key)),
             value: Set(value),
             expiration: Set(expiration),
         };
 
-        cache.insert(&self.db).await.is_ok()
+        Entity::insert(cache)
+            .on_conflict(
+                OnConflict::column(Column::Key)
+                    .update_columns([Column::Value, Column::Expiration])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await
+            .is_ok()
     }
 
     async fn forget(&self, key: &str) -> bool {
         Entity::delete_many()
-            .filter(Column::Key.eq(key))
+            .filter(Column::Key.eq(self.key(key)))
             .exec(&self.db)
             .await
             .map(|res| res.rows_affected > 0)
@@ -93,7 +202,12 @@ impl CacheStore for DatabaseStore {
     }
 
     async fn flush(&self) -> bool {
-        Entity::delete_many()
+        let mut delete = Entity::delete_many();
+        if !self.prefix.is_empty() {
+            let pattern = format!("{}%", self.prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            delete = delete.filter(Expr::col(Column::Key).like(LikeExpr::new(pattern).escape('\\')));
+        }
+        delete
             .exec(&self.db)
             .await
             .map(|res| res.rows_affected > 0)
@@ -102,7 +216,7 @@ impl CacheStore for DatabaseStore {
 
     async fn has(&self, key: &str) -> bool {
         Entity::find()
-            .filter(Column::Key.eq(key))
+            .filter(Column::Key.eq(self.key(key)))
             .one(&self.db)
             .await
             .map(|res| res.is_some())
@@ -110,18 +224,157 @@ impl CacheStore for DatabaseStore {
     }
 
     async fn increment(&self, key: &str, value: i64) -> i64 {
-        if let Some(current) = self.get(key).await {
-            if let Some(current) = current.as_i64() {
-                let new_value = current + value;
-                if self.put(key, Value::from(new_value), None).await {
-                    return new_value;
-                }
+        match self.add_to_counter(key, value).await {
+            Ok(counter) => counter,
+            Err(e) => {
+                eprintln!("Failed to increment cache key {}: {}", key, e);
+                0
             }
         }
-        0
     }
 
     async fn decrement(&self, key: &str, value: i64) -> i64 {
         self.increment(key, -value).await
     }
-} 
\ No newline at end of file
+
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        let now = Self::now();
+        let key = self.key(key);
+
+        // Clear an expired entry so it doesn't block the insert
+        let _ = Entity::delete_many()
+            .filter(Column::Key.eq(&key))
+            .filter(Column::Expiration.lt(now))
+            .exec(&self.db)
+            .await;
+
+        let value = match self.codec.encode_text(&value) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+
+        // The primary key makes the insert fail if another caller stored the key first
+        let cache = ActiveModel {
+            key: Set(key),
+            value: Set(value),
+            expiration: Set(Self::get_expiration(ttl)),
+        };
+        cache.insert(&self.db).await.is_ok()
+    }
+
+    async fn prune(&self) -> u64 {
+        let now = Self::now();
+        let mut pruned = 0;
+        if let Ok(res) = Entity::delete_many().filter(Column::Expiration.lt(now)).exec(&self.db).await {
+            pruned += res.rows_affected;
+        }
+        if let Ok(res) = cache_lock::Entity::delete_many()
+            .filter(cache_lock::Column::Expiration.lt(now))
+            .exec(&self.db)
+            .await
+        {
+            pruned += res.rows_affected;
+        }
+        pruned
+    }
+
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        let now = Self::now();
+
+        // Clear an expired lock so it doesn't block the insert
+        let _ = cache_lock::Entity::delete_many()
+            .filter(cache_lock::Column::Key.eq(self.key(name)))
+            .filter(cache_lock::Column::Expiration.lt(now))
+            .exec(&self.db)
+            .await;
+
+        // The primary key makes the insert fail while someone else holds the lock
+        let lock = cache_lock::ActiveModel {
+            key: Set(self.key(name)),
+            owner: Set(owner.to_string()),
+            expiration: Set(Self::get_expiration(ttl)),
+        };
+        lock.insert(&self.db).await.is_ok()
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        cache_lock::Entity::delete_many()
+            .filter(cache_lock::Column::Key.eq(self.key(name)))
+            .filter(cache_lock::Column::Owner.eq(owner))
+            .exec(&self.db)
+            .await
+            .map(|res| res.rows_affected > 0)
+            .unwrap_or(false)
+    }
+
+    async fn force_release_lock(&self, name: &str) {
+        let _ = cache_lock::Entity::delete_many()
+            .filter(cache_lock::Column::Key.eq(self.key(name)))
+            .exec(&self.db)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::cache::codec::Serializer;
+    use crate::framework::testing::{create_table, sqlite_database};
+    use std::sync::Arc;
+
+    async fn store() -> DatabaseStore {
+        let db = sqlite_database("sqlite::memory:").await;
+        create_table(&db, Entity).await;
+        create_table(&db, cache_lock::Entity).await;
+        DatabaseStore::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_put_overwrites_and_counters_are_atomic() {
+        let store = Arc::new(store().await);
+        assert!(store.put("greeting", Value::from("hello"), None).await);
+        assert!(store.put("greeting", Value::from("hi"), Some(Duration::from_secs(60))).await);
+        assert_eq!(store.get("greeting").await, Some(Value::from("hi")));
+
+        let increments: Vec<_> = (0..10)
+            .map(|_| {
+                let store = store.clone();
+                tokio::spawn(async move { store.increment("visits", 1).await })
+            })
+            .collect();
+        for increment in increments {
+            increment.await.unwrap();
+        }
+        assert_eq!(store.get("visits").await, Some(Value::from(10)));
+        assert_eq!(store.decrement("visits", 3).await, 7);
+
+        // An expired counter starts over
+        store.put("expired", Value::from(5), Some(Duration::ZERO)).await;
+        store.put("stale", Value::from("old"), Some(Duration::ZERO)).await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(store.increment("expired", 2).await, 2);
+
+        assert_eq!(store.prune().await, 1);
+        assert!(!store.has("stale").await);
+        assert!(store.has("greeting").await);
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_stores_keep_their_items_apart() {
+        let shared = store().await;
+        let db = shared.db.clone();
+        let app = DatabaseStore::new(db.clone()).prefix("app_1:");
+        let other = DatabaseStore::new(db).prefix("app%1:").codec(Codec::new(Serializer::MessagePack).compress_above(16));
+
+        let report = Value::from(vec!["row"; 50]);
+        assert!(app.put("report", Value::from("app"), None).await);
+        assert!(other.put("report", report.clone(), None).await);
+        assert_eq!(other.increment("visits", 2).await, 2);
+        assert_eq!(app.get("report").await, Some(Value::from("app")));
+        assert_eq!(other.get("report").await, Some(report));
+
+        assert!(other.flush().await);
+        assert!(!other.has("report").await);
+        assert_eq!(app.get("report").await, Some(Value::from("app")));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::framework::cache::CacheStore;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+    size: usize,
+    /// Position in the recency order, higher is more recently used
+    used: u64,
+}
+
+impl Entry {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+    /// Keys by the last time they were used, oldest first
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    bytes: usize,
+    locks: HashMap<String, (String, Option<Instant>)>,
+}
+
+impl State {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.used);
+            entry.used = clock;
+            self.recency.insert(clock, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.used);
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    /// Get a live entry, dropping it if it has expired
+    fn live(&mut self, key: &str) -> Option<&mut Entry> {
+        if self.entries.get(key)?.expired(Instant::now()) {
+            self.remove(key);
+            return None;
+        }
+        self.touch(key);
+        self.entries.get_mut(key)
+    }
+
+    fn insert(&mut self, key: &str, value: Value, expires_at: Option<Instant>) {
+        self.remove(key);
+        self.clock += 1;
+        let size = key.len() + value.to_string().len();
+        self.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires_at,
+                size,
+                used: self.clock,
+            },
+        );
+        self.recency.insert(self.clock, key.to_string());
+        self.bytes += size;
+    }
+
+    /// Evict the least recently used entries until the store is within its limits
+    fn evict(&mut self, max_entries: Option<usize>, max_bytes: Option<usize>) {
+        while max_entries.is_some_and(|max| self.entries.len() > max)
+            || max_bytes.is_some_and(|max| self.bytes > max)
+        {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.size;
+            }
+        }
+    }
+}
+
+/// An in-process cache store that evicts the least recently used items once it's full.
+/// Items are lost when the process exits and aren't shared between processes.
+pub struct MemoryStore {
+    state: Mutex<State>,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            max_entries: None,
+            max_bytes: None,
+        }
+    }
+
+    /// Limit the number of items held
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Limit the approximate size of the keys and serialized values held
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn put_entry(&self, state: &mut State, key: &str, value: Value, ttl: Option<Duration>) {
+        state.insert(key, value, ttl.map(|ttl| Instant::now() + ttl));
+        state.evict(self.max_entries, self.max_bytes);
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryStore {
+    async fn get(&self, key: &str) -> Option<Value> {
+        let mut state = self.state.lock().unwrap();
+        state.live(key).map(|entry| entry.value.clone())
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state
+            .live(key)
+            .map(|entry| (entry.value.clone(), entry.expires_at.map(|expires_at| expires_at - now)))
+    }
+
+    async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.put_entry(&mut state, key, value, ttl);
+        true
+    }
+
+    async fn forget(&self, key: &str) -> bool {
+        self.state.lock().unwrap().remove(key).is_some()
+    }
+
+    async fn flush(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.recency.clear();
+        state.bytes = 0;
+        true
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        self.state.lock().unwrap().live(key).is_some()
+    }
+
+    async fn increment(&self, key: &str, value: i64) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        let (current, expires_at) = match state.live(key) {
+            Some(entry) => (entry.value.as_i64().unwrap_or(0), entry.expires_at),
+            None => (0, None),
+        };
+        let new_value = current + value;
+        state.insert(key, Value::from(new_value), expires_at);
+        state.evict(self.max_entries, self.max_bytes);
+        new_value
+    }
+
+    async fn decrement(&self, key: &str, value: i64) -> i64 {
+        self.increment(key, -value).await
+    }
+
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.live(key).is_some() {
+            return false;
+        }
+        self.put_entry(&mut state, key, value, ttl);
+        true
+    }
+
+    async fn prune(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let expired: Vec<String> = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            state.remove(key);
+        }
+        expired.len() as u64
+    }
+
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        // Locks are kept apart from the entries so they are never evicted
+        if let Some((_, Some(expires_at))) = state.locks.get(name) {
+            if *expires_at <= now {
+                state.locks.remove(name);
+            }
+        }
+        if state.locks.contains_key(name) {
+            return false;
+        }
+        state.locks.insert(name.to_string(), (owner.to_string(), ttl.map(|ttl| now + ttl)));
+        true
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let held = matches!(
+            state.locks.get(name),
+            Some((holder, expires_at)) if holder == owner && expires_at.is_none_or(|at| at > Instant::now())
+        );
+        if held {
+            state.locks.remove(name);
+        }
+        held
+    }
+
+    async fn force_release_lock(&self, name: &str) {
+        self.state.lock().unwrap().locks.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_entries() {
+        let store = MemoryStore::new().max_entries(2);
+        store.put("a", Value::from(1), None).await;
+        store.put("b", Value::from(2), None).await;
+        assert_eq!(store.get("a").await, Some(Value::from(1)));
+
+        store.put("c", Value::from(3), None).await;
+        assert!(store.has("a").await);
+        assert!(!store.has("b").await);
+        assert!(store.has("c").await);
+
+        let store = MemoryStore::new().max_bytes(20);
+        store.put("first", Value::from("0123456789"), None).await;
+        store.put("second", Value::from("0123456789"), None).await;
+        assert!(!store.has("first").await);
+        assert!(store.has("second").await);
+    }
+
+    #[tokio::test]
+    async fn test_expires_entries() {
+        let store = MemoryStore::new();
+        store.put("short", Value::from("gone"), Some(Duration::from_millis(10))).await;
+        assert_eq!(store.increment("counter", 5).await, 5);
+        assert_eq!(store.decrement("counter", 2).await, 3);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(store.get("short").await, None);
+        assert!(store.add("short", Value::from("back"), None).await);
+        assert!(!store.add("short", Value::from("again"), None).await);
+        assert_eq!(store.get("counter").await, Some(Value::from(3)));
+    }
+}
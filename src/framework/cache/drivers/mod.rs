@@ -1,5 +1,9 @@
 pub mod database;
+pub mod memory;
 pub mod redis;
+pub mod tiered;
 
 pub use database::DatabaseStore;
-pub use redis::RedisStore; 
\ No newline at end of file
+pub use memory::MemoryStore;
+pub use redis::RedisStore;
+pub use tiered::TieredStore;
\ No newline at end of file
@@ -1,17 +1,61 @@
+use crate::framework::cache::codec::Codec;
 use crate::framework::cache::CacheStore;
 use async_trait::async_trait;
-use redis::{AsyncCommands, Client, RedisError};
+use redis::{AsyncCommands, Client, RedisError, Script};
 use serde_json::Value;
 use std::time::Duration;
 
 pub struct RedisStore {
     client: Client,
+    prefix: String,
+    codec: Codec,
 }
 
 impl RedisStore {
     pub fn new(url: &str) -> Result<Self, redis::RedisError> {
         let client = Client::open(url)?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            prefix: String::new(),
+            codec: Codec::default(),
+        })
+    }
+
+    /// Prefix every key, so apps sharing a Redis database don't see or flush each other's items
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// How values are serialized and compressed
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    fn lock_key(&self, name: &str) -> String {
+        self.key(&format!("lock:{}", name))
+    }
+
+    fn tag_members_key(&self, tag: &str) -> String {
+        self.key(&format!("tag:{}:entries", tag))
+    }
+
+    /// A SCAN pattern matching every key under the prefix
+    fn prefix_pattern(&self) -> String {
+        let mut pattern = String::new();
+        for c in self.prefix.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('*');
+        pattern
     }
 }
 
@@ -19,8 +63,21 @@ impl RedisStore {
 impl CacheStore for RedisStore {
     async fn get(&self, key: &str) -> Option<Value> {
         let mut conn = self.client.get_async_connection().await.ok()?;
-        let value: Option<String> = conn.get(key).await.ok()?;
-        value.and_then(|v| serde_json::from_str(&v).ok())
+        let value: Option<Vec<u8>> = conn.get(self.key(key)).await.ok()?;
+        value.and_then(|v| self.codec.decode(&v))
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
+        let mut conn = self.client.get_async_connection().await.ok()?;
+        let (value, ttl_ms): (Option<Vec<u8>>, i64) = redis::pipe()
+            .get(self.key(key))
+            .pttl(self.key(key))
+            .query_async(&mut conn)
+            .await
+            .ok()?;
+        // PTTL is -1 for a key without an expiry
+        let ttl = u64::try_from(ttl_ms).ok().map(Duration::from_millis);
+        Some((self.codec.decode(&value?)?, ttl))
     }
 
     async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
@@ -29,11 +86,12 @@ impl CacheStore for RedisStore {
             Err(_) => return false,
         };
 
-        let value = match serde_json::to_string(&value) {
+        let value = match self.codec.encode(&value) {
             Ok(v) => v,
             Err(_) => return false,
         };
 
+        let key = self.key(key);
         let set_result: Result<(), RedisError> = if let Some(ttl) = ttl {
             conn.set_ex(key, value, ttl.as_secs() as u64).await
         } else {
@@ -45,26 +103,45 @@ impl CacheStore for RedisStore {
 
     async fn forget(&self, key: &str) -> bool {
         if let Ok(mut conn) = self.client.get_async_connection().await {
-            conn.del(key).await.unwrap_or(false)
+            conn.del(self.key(key)).await.unwrap_or(false)
         } else {
             false
         }
     }
 
     async fn flush(&self) -> bool {
-        if let Ok(mut conn) = self.client.get_async_connection().await {
-            let result: Result<(), RedisError> = redis::cmd("FLUSHDB")
-                .query_async(&mut conn)
-                .await;
-            result.is_ok()
-        } else {
-            false
+        let Ok(mut conn) = self.client.get_async_connection().await else {
+            return false;
+        };
+
+        // Without a prefix every key in the database belongs to the cache
+        if self.prefix.is_empty() {
+            let result: Result<(), RedisError> = redis::cmd("FLUSHDB").query_async(&mut conn).await;
+            return result.is_ok();
         }
+
+        let keys: Vec<String> = match conn.scan_match::<_, String>(self.prefix_pattern()).await {
+            Ok(mut iter) => {
+                let mut keys = Vec::new();
+                while let Some(key) = iter.next_item().await {
+                    keys.push(key);
+                }
+                keys
+            }
+            Err(_) => return false,
+        };
+        for chunk in keys.chunks(1000) {
+            let result: Result<(), RedisError> = conn.del(chunk).await;
+            if result.is_err() {
+                return false;
+            }
+        }
+        true
     }
 
     async fn has(&self, key: &str) -> bool {
         if let Ok(mut conn) = self.client.get_async_connection().await {
-            conn.exists(key).await.unwrap_or(false)
+            conn.exists(self.key(key)).await.unwrap_or(false)
         } else {
             false
         }
@@ -72,7 +149,7 @@ impl CacheStore for RedisStore {
 
     async fn increment(&self, key: &str, value: i64) -> i64 {
         if let Ok(mut conn) = self.client.get_async_connection().await {
-            conn.incr(key, value).await.unwrap_or(0)
+            conn.incr(self.key(key), value).await.unwrap_or(0)
         } else {
             0
         }
@@ -80,9 +157,98 @@ impl CacheStore for RedisStore {
 
     async fn decrement(&self, key: &str, value: i64) -> i64 {
         if let Ok(mut conn) = self.client.get_async_connection().await {
-            conn.decr(key, value).await.unwrap_or(0)
+            conn.decr(self.key(key), value).await.unwrap_or(0)
         } else {
             0
         }
     }
+
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        let mut conn = match self.client.get_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => return false,
+        };
+
+        let value = match self.codec.encode(&value) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(key)).arg(value).arg("NX");
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis() as u64);
+        }
+        let result: Result<Option<String>, RedisError> = cmd.query_async(&mut conn).await;
+        matches!(result, Ok(Some(_)))
+    }
+
+    async fn add_to_tag(&self, tag: &str, key: &str, ttl: Option<Duration>) {
+        let Ok(mut conn) = self.client.get_async_connection().await else {
+            return;
+        };
+
+        // Members are scored by their expiry so expired ones can be pruned as the set is written
+        let now = chrono::Utc::now().timestamp();
+        let score = ttl.map(|ttl| (now + ttl.as_secs() as i64) as f64).unwrap_or(f64::INFINITY);
+        let members = self.tag_members_key(tag);
+        let _: Result<(), RedisError> = redis::pipe()
+            .zadd(&members, self.key(key), score)
+            .ignore()
+            .zrembyscore(&members, "-inf", now)
+            .ignore()
+            .query_async(&mut conn)
+            .await;
+    }
+
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        let Ok(mut conn) = self.client.get_async_connection().await else {
+            return false;
+        };
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.lock_key(name)).arg(owner).arg("NX");
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis().max(1) as u64);
+        }
+        let result: Result<Option<String>, RedisError> = cmd.query_async(&mut conn).await;
+        matches!(result, Ok(Some(_)))
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        let Ok(mut conn) = self.client.get_async_connection().await else {
+            return false;
+        };
+
+        // Compare and delete in one step so a lock that expired and was taken over isn't released
+        let script = Script::new(
+            r#"
+            if redis.call('GET', KEYS[1]) == ARGV[1] then
+                return redis.call('DEL', KEYS[1])
+            end
+            return 0
+            "#,
+        );
+        let released: Result<i64, RedisError> = script.key(self.lock_key(name)).arg(owner).invoke_async(&mut conn).await;
+        matches!(released, Ok(1))
+    }
+
+    async fn force_release_lock(&self, name: &str) {
+        if let Ok(mut conn) = self.client.get_async_connection().await {
+            let _: Result<(), RedisError> = conn.del(self.lock_key(name)).await;
+        }
+    }
+
+    async fn flush_tag(&self, tag: &str) {
+        let Ok(mut conn) = self.client.get_async_connection().await else {
+            return;
+        };
+
+        let members = self.tag_members_key(tag);
+        let keys: Vec<String> = conn.zrange(&members, 0, -1).await.unwrap_or_default();
+        for chunk in keys.chunks(1000) {
+            let _: Result<(), RedisError> = conn.del(chunk).await;
+        }
+        let _: Result<(), RedisError> = conn.del(&members).await;
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,188 @@
+use crate::framework::cache::drivers::MemoryStore;
+use crate::framework::cache::CacheStore;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::time::Duration;
+
+/// A cache store that keeps recently read items in process memory in front of a shared
+/// store such as Redis.
+///
+/// Writes go to both tiers and reads fall back to the shared store, copying what they find
+/// into memory for `local_ttl`, or for the time the item has left if that's shorter. Other processes only see a write once their local copy
+/// expires, so keep `local_ttl` short. Locks, counters and tags go to the shared store.
+pub struct TieredStore {
+    local: MemoryStore,
+    shared: Box<dyn CacheStore + Send + Sync>,
+    local_ttl: Duration,
+}
+
+impl TieredStore {
+    pub fn new(local: MemoryStore, shared: Box<dyn CacheStore + Send + Sync>) -> Self {
+        Self {
+            local,
+            shared,
+            local_ttl: Duration::from_secs(5),
+        }
+    }
+
+    /// How long items are kept in memory at most
+    pub fn local_ttl(mut self, ttl: Duration) -> Self {
+        self.local_ttl = ttl;
+        self
+    }
+
+    fn local_ttl_for(&self, ttl: Option<Duration>) -> Duration {
+        ttl.map_or(self.local_ttl, |ttl| ttl.min(self.local_ttl))
+    }
+}
+
+#[async_trait]
+impl CacheStore for TieredStore {
+    async fn get(&self, key: &str) -> Option<Value> {
+        if let Some(value) = self.local.get(key).await {
+            return Some(value);
+        }
+
+        let (value, ttl) = self.shared.get_with_ttl(key).await?;
+        self.local.put(key, value.clone(), Some(self.local_ttl_for(ttl))).await;
+        Some(value)
+    }
+
+    async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        if !self.shared.put(key, value.clone(), ttl).await {
+            self.local.forget(key).await;
+            return false;
+        }
+        self.local.put(key, value, Some(self.local_ttl_for(ttl))).await
+    }
+
+    async fn forget(&self, key: &str) -> bool {
+        let local = self.local.forget(key).await;
+        self.shared.forget(key).await || local
+    }
+
+    async fn flush(&self) -> bool {
+        self.local.flush().await;
+        self.shared.flush().await
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        self.local.has(key).await || self.shared.has(key).await
+    }
+
+    async fn increment(&self, key: &str, value: i64) -> i64 {
+        // Counters change too often to be copied, so they always come from the shared store
+        self.local.forget(key).await;
+        self.shared.increment(key, value).await
+    }
+
+    async fn decrement(&self, key: &str, value: i64) -> i64 {
+        self.local.forget(key).await;
+        self.shared.decrement(key, value).await
+    }
+
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        if !self.shared.add(key, value.clone(), ttl).await {
+            return false;
+        }
+        self.local.put(key, value, Some(self.local_ttl_for(ttl))).await
+    }
+
+    async fn prune(&self) -> u64 {
+        self.local.prune().await;
+        self.shared.prune().await
+    }
+
+    async fn add_to_tag(&self, tag: &str, key: &str, ttl: Option<Duration>) {
+        self.shared.add_to_tag(tag, key, ttl).await;
+    }
+
+    async fn flush_tag(&self, tag: &str) {
+        self.shared.flush_tag(tag).await;
+    }
+
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        self.shared.acquire_lock(name, owner, ttl).await
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        self.shared.release_lock(name, owner).await
+    }
+
+    async fn force_release_lock(&self, name: &str) {
+        self.shared.force_release_lock(name).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Lets the test write to the shared tier behind the tiered store's back
+    struct Shared(Arc<MemoryStore>);
+
+    #[async_trait]
+    impl CacheStore for Shared {
+        async fn get(&self, key: &str) -> Option<Value> {
+            self.0.get(key).await
+        }
+        async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
+            self.0.get_with_ttl(key).await
+        }
+        async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+            self.0.put(key, value, ttl).await
+        }
+        async fn forget(&self, key: &str) -> bool {
+            self.0.forget(key).await
+        }
+        async fn flush(&self) -> bool {
+            self.0.flush().await
+        }
+        async fn has(&self, key: &str) -> bool {
+            self.0.has(key).await
+        }
+        async fn increment(&self, key: &str, value: i64) -> i64 {
+            self.0.increment(key, value).await
+        }
+        async fn decrement(&self, key: &str, value: i64) -> i64 {
+            self.0.decrement(key, value).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reads_through_to_the_shared_store() {
+        let shared = Arc::new(MemoryStore::new());
+        let store = TieredStore::new(MemoryStore::new(), Box::new(Shared(shared.clone())))
+            .local_ttl(Duration::from_millis(50));
+
+        shared.put("users:all", Value::from("v1"), None).await;
+        assert_eq!(store.get("users:all").await, Some(Value::from("v1")));
+
+        // Served from memory until the local copy expires
+        shared.put("users:all", Value::from("v2"), None).await;
+        assert_eq!(store.get("users:all").await, Some(Value::from("v1")));
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(store.get("users:all").await, Some(Value::from("v2")));
+
+        assert!(store.put("users:1", Value::from("jane"), None).await);
+        assert_eq!(shared.get("users:1").await, Some(Value::from("jane")));
+
+        assert!(store.forget("users:all").await);
+        assert_eq!(store.get("users:all").await, None);
+        assert_eq!(shared.get("users:all").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_local_copies_expire_with_the_shared_item() {
+        let shared = Arc::new(MemoryStore::new());
+        let store = TieredStore::new(MemoryStore::new(), Box::new(Shared(shared.clone())))
+            .local_ttl(Duration::from_secs(60));
+
+        shared.put("report", Value::from("q3"), Some(Duration::from_millis(50))).await;
+        assert_eq!(store.get("report").await, Some(Value::from("q3")));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(store.get("report").await, None);
+    }
+}
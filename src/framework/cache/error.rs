@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("Failed to compute the value for cache key {key}: {source}")]
+    Callback {
+        key: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Failed to serialize the value for cache key {key}: {source}")]
+    Serialization {
+        key: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("The cache store did not write key {0}")]
+    WriteFailed(String),
+}
@@ -0,0 +1,113 @@
+use crate::framework::cache::drivers::MemoryStore;
+use crate::framework::cache::{Cache, CacheStore};
+use crate::framework::testing::fake::{FakeGuard, FakeSlot};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+type SharedStore = Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>;
+type Recorded = Arc<Mutex<Vec<(String, Value)>>>;
+
+static FAKE: FakeSlot<(SharedStore, Recorded)> = FakeSlot::new();
+
+/// An in-memory cache store that records the items written to it
+pub struct FakeStore {
+    store: MemoryStore,
+    written: Recorded,
+}
+
+impl FakeStore {
+    fn record(&self, key: &str, value: Value) {
+        self.written.lock().unwrap().push((key.to_string(), value));
+    }
+}
+
+#[async_trait]
+impl CacheStore for FakeStore {
+    async fn get(&self, key: &str) -> Option<Value> {
+        self.store.get(key).await
+    }
+
+    async fn put(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        self.record(key, value.clone());
+        self.store.put(key, value, ttl).await
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
+        self.store.get_with_ttl(key).await
+    }
+
+    async fn forget(&self, key: &str) -> bool {
+        self.store.forget(key).await
+    }
+
+    async fn flush(&self) -> bool {
+        self.store.flush().await
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        self.store.has(key).await
+    }
+
+    async fn increment(&self, key: &str, value: i64) -> i64 {
+        let counter = self.store.increment(key, value).await;
+        self.record(key, Value::from(counter));
+        counter
+    }
+
+    async fn decrement(&self, key: &str, value: i64) -> i64 {
+        self.increment(key, -value).await
+    }
+
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        let added = self.store.add(key, value.clone(), ttl).await;
+        if added {
+            self.record(key, value);
+        }
+        added
+    }
+
+    async fn prune(&self) -> u64 {
+        self.store.prune().await
+    }
+
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        self.store.acquire_lock(name, owner, ttl).await
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        self.store.release_lock(name, owner).await
+    }
+
+    async fn force_release_lock(&self, name: &str) {
+        self.store.force_release_lock(name).await;
+    }
+}
+
+/// The fake store, if one is installed
+pub(crate) fn current() -> Option<SharedStore> {
+    FAKE.current().map(|(store, _)| store)
+}
+
+impl Cache {
+    /// Replace the cache stores with an empty in-memory store until the returned guard is
+    /// dropped. Other tests that fake the cache wait for the guard.
+    pub fn fake() -> FakeGuard {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let store: Box<dyn CacheStore + Send + Sync> = Box::new(FakeStore {
+            store: MemoryStore::new(),
+            written: written.clone(),
+        });
+        FAKE.install((Arc::new(RwLock::new(store)), written))
+    }
+
+    /// Get every key and value written to the fake cache, in the order they were written
+    pub fn written() -> Vec<(String, Value)> {
+        match FAKE.current() {
+            Some((_, written)) => written.lock().unwrap().clone(),
+            None => panic!("Cache is not faked, call Cache::fake() first"),
+        }
+    }
+}
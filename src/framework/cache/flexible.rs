@@ -0,0 +1,88 @@
+use crate::framework::cache::{CacheStore, Lock};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+type SharedStore = Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>;
+
+/// How long a refresh may hold its lock, in case the task dies before releasing it
+const REFRESH_LOCK_TTL: Duration = Duration::from_secs(60);
+
+/// A flexible value stored together with the time it was created, in milliseconds, so the
+/// two can't expire or be evicted apart
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    value: Value,
+    created_at: i64,
+}
+
+pub(super) fn refresh_lock(store: &SharedStore, key: &str) -> Lock {
+    Lock::new(store.clone(), &format!("flexible:refresh:{}", key), Some(REFRESH_LOCK_TTL), None)
+}
+
+/// Get a flexible value and whether it was stored less than `ttl` ago. Anything else stored
+/// under the key is treated as missing.
+pub(super) async fn get(store: &SharedStore, key: &str, ttl: Duration) -> Option<(Value, bool)> {
+    let envelope: Envelope = serde_json::from_value(store.read().await.get(key).await?).ok()?;
+    let fresh = Utc::now().timestamp_millis() - envelope.created_at < ttl.as_millis() as i64;
+    Some((envelope.value, fresh))
+}
+
+/// Whether the value was stored less than `ttl` ago
+pub(super) async fn is_fresh(store: &SharedStore, key: &str, ttl: Duration) -> bool {
+    get(store, key, ttl).await.is_some_and(|(_, fresh)| fresh)
+}
+
+/// Store a value together with the time it was created, keeping it through the grace period
+pub(super) async fn store(store: &SharedStore, key: &str, value: Value, ttl: Duration, grace: Duration) {
+    let envelope = Envelope {
+        value,
+        created_at: Utc::now().timestamp_millis(),
+    };
+    if let Ok(envelope) = serde_json::to_value(envelope) {
+        store.read().await.put(key, envelope, Some(ttl + grace)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framework::cache::config::{init_cache, CacheConfig, CacheDriver};
+    use crate::framework::cache::Cache;
+    use futures_util::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_flexible_refreshes_once_when_stale() {
+        let config = CacheConfig {
+            driver: CacheDriver::Memory,
+            ..Default::default()
+        };
+        init_cache(config, None).await.unwrap();
+
+        let key = format!("flexible-test:{}", uuid::Uuid::new_v4());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flexible = || {
+            let calls = calls.clone();
+            Cache::flexible(&key, Duration::from_millis(100), Duration::from_secs(60), move || async move {
+                calls.fetch_add(1, Ordering::SeqCst) + 1
+            })
+        };
+
+        assert_eq!(flexible().await, Some(1));
+        assert_eq!(flexible().await, Some(1));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let stale = join_all((0..10).map(|_| flexible())).await;
+        assert!(stale.iter().all(|value| *value == Some(1)));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(flexible().await, Some(2));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::framework::cache::CacheStore;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+type SharedStore = Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>;
+
+/// How long `block` waits between attempts to acquire a lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// An atomic lock held in the cache store, identified by a name and owned by a random token.
+///
+/// Only the owner can release the lock, so a process that has to release a lock acquired
+/// elsewhere can pass the owner token along and get it back with `Cache::restore_lock`.
+pub struct Lock {
+    store: SharedStore,
+    name: String,
+    owner: String,
+    ttl: Option<Duration>,
+}
+
+impl Lock {
+    /// Create a lock; a `ttl` of `None` holds the lock until it's released
+    pub fn new(store: SharedStore, name: &str, ttl: Option<Duration>, owner: Option<String>) -> Self {
+        Self {
+            store,
+            name: name.to_string(),
+            owner: owner.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            ttl,
+        }
+    }
+
+    /// The token identifying the owner of the lock
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Try to acquire the lock, returning whether it was acquired
+    pub async fn get(&self) -> bool {
+        self.store.read().await.acquire_lock(&self.name, &self.owner, self.ttl).await
+    }
+
+    /// Acquire the lock, run the callback and release the lock again. Returns `None` without
+    /// running the callback if the lock is held by someone else.
+    pub async fn run<T, F, Fut>(&self, callback: F) -> Option<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        if !self.get().await {
+            return None;
+        }
+        let result = callback().await;
+        self.release().await;
+        Some(result)
+    }
+
+    /// Wait up to `timeout` for the lock to become free, returning whether it was acquired
+    pub async fn block(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        loop {
+            if self.get().await {
+                return true;
+            }
+            if started.elapsed() + RETRY_INTERVAL > timeout {
+                return false;
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Release the lock if it is still held by this owner
+    pub async fn release(&self) -> bool {
+        self.store.read().await.release_lock(&self.name, &self.owner).await
+    }
+
+    /// Release the lock whoever holds it
+    pub async fn force_release(&self) {
+        self.store.read().await.force_release_lock(&self.name).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::cache::drivers::database::{cache_lock, DatabaseStore};
+    use crate::framework::testing::{create_table, sqlite_database};
+
+    async fn store() -> SharedStore {
+        let db = sqlite_database("sqlite::memory:").await;
+        create_table(&db, cache_lock::Entity).await;
+        Arc::new(RwLock::new(Box::new(DatabaseStore::new(db))))
+    }
+
+    #[tokio::test]
+    async fn test_locks_are_exclusive_to_their_owner() {
+        let store = store().await;
+        let lock = Lock::new(store.clone(), "import", Some(Duration::from_secs(60)), None);
+        let other = Lock::new(store.clone(), "import", Some(Duration::from_secs(60)), None);
+
+        assert!(lock.get().await);
+        assert!(!other.get().await);
+        assert!(!other.block(Duration::from_millis(300)).await);
+        assert!(!other.release().await);
+
+        let restored = Lock::new(store.clone(), "import", None, Some(lock.owner().to_string()));
+        assert!(restored.release().await);
+        assert_eq!(other.run(|| async { 42 }).await, Some(42));
+        assert!(lock.get().await);
+
+        let expiring = Lock::new(store.clone(), "report", Some(Duration::from_secs(1)), None);
+        assert!(expiring.get().await);
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        assert!(Lock::new(store, "report", None, None).get().await);
+    }
+}
@@ -7,9 +7,22 @@ use tokio::sync::RwLock;
 use once_cell::sync::OnceCell;
 use std::future::Future;
 use std::pin::Pin;
+use std::collections::HashMap;
 
 pub mod drivers;
 pub mod config;
+pub mod tags;
+pub mod lock;
+pub mod repository;
+pub mod codec;
+pub mod error;
+pub mod fake;
+mod flexible;
+
+pub use tags::TaggedCache;
+pub use lock::Lock;
+pub use repository::Repository;
+pub use error::CacheError;
 
 /// A trait to easily box futures for the cache system
 pub trait BoxFuture<T>: Future<Output = T> + Send + 'static {
@@ -26,6 +39,7 @@ where
 }
 
 static CACHE_STORE: OnceCell<Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>> = OnceCell::new();
+static NAMED_STORES: OnceCell<HashMap<String, Repository>> = OnceCell::new();
 
 #[async_trait]
 pub trait CacheStore {
@@ -36,6 +50,56 @@ pub trait CacheStore {
     async fn has(&self, key: &str) -> bool;
     async fn increment(&self, key: &str, value: i64) -> i64;
     async fn decrement(&self, key: &str, value: i64) -> i64;
+
+    /// Get an item together with the time it has left, `None` if it doesn't expire.
+    /// Stores that don't track expiry report every item as never expiring.
+    async fn get_with_ttl(&self, key: &str) -> Option<(Value, Option<Duration>)> {
+        self.get(key).await.map(|value| (value, None))
+    }
+
+    /// Store an item only if the key doesn't exist yet, returning whether it was stored.
+    /// Stores should override this with an atomic operation.
+    async fn add(&self, key: &str, value: Value, ttl: Option<Duration>) -> bool {
+        if self.has(key).await {
+            false
+        } else {
+            self.put(key, value, ttl).await
+        }
+    }
+
+    /// Delete expired items, returning how many were removed. Stores that expire items
+    /// on their own have nothing to prune.
+    async fn prune(&self) -> u64 {
+        0
+    }
+
+    /// Record that `key` was stored under `tag`, so flushing the tag can delete it. Stores
+    /// that don't track members rely on the tag's version changing instead.
+    async fn add_to_tag(&self, _tag: &str, _key: &str, _ttl: Option<Duration>) {}
+
+    /// Delete the items recorded under a tag
+    async fn flush_tag(&self, _tag: &str) {}
+
+    /// Acquire a lock if it's free, returning whether it was acquired.
+    /// Stores should override the lock methods with atomic operations.
+    async fn acquire_lock(&self, name: &str, owner: &str, ttl: Option<Duration>) -> bool {
+        self.add(&format!("lock:{}", name), Value::from(owner), ttl).await
+    }
+
+    /// Release a lock if it's held by the given owner
+    async fn release_lock(&self, name: &str, owner: &str) -> bool {
+        let key = format!("lock:{}", name);
+        if self.get(&key).await.as_ref().and_then(Value::as_str) == Some(owner) {
+            self.forget(&key).await
+        } else {
+            false
+        }
+    }
+
+    /// Release a lock whoever holds it
+    async fn force_release_lock(&self, name: &str) {
+        self.forget(&format!("lock:{}", name)).await;
+    }
 }
 
 /// A Laravel-like Cache facade for easy caching operations
@@ -44,71 +108,94 @@ pub struct Cache;
 impl Cache {
     /// Get the underlying cache store
     pub fn store() -> Arc<RwLock<Box<dyn CacheStore + Send + Sync>>> {
+        if let Some(fake) = fake::current() {
+            return fake;
+        }
+
         Arc::clone(CACHE_STORE.get().expect("Cache store not initialized"))
     }
 
+    /// Get one of the stores configured under `stores`, e.g. `Cache::store_named("reports")`
+    pub fn store_named(name: &str) -> Repository {
+        // Every named store shares the fake, so tests don't need to configure them
+        if let Some(fake) = fake::current() {
+            return Repository::new(fake);
+        }
+
+        NAMED_STORES
+            .get()
+            .and_then(|stores| stores.get(name))
+            .cloned()
+            .unwrap_or_else(|| panic!("Cache store [{}] is not defined", name))
+    }
+
+    fn repository() -> Repository {
+        Repository::new(Self::store())
+    }
+
+    /// Scope cache operations to a set of tags, e.g. `Cache::tags(&["users"]).flush()`
+    pub fn tags(tags: &[&str]) -> TaggedCache {
+        Self::repository().tags(tags)
+    }
+
+    /// Get an atomic lock; a zero `ttl` holds the lock until it's released
+    pub fn lock(name: &str, ttl: Duration) -> Lock {
+        Self::repository().lock(name, ttl)
+    }
+
+    /// Get a lock acquired elsewhere by its owner token, e.g. to release it from another process
+    pub fn restore_lock(name: &str, owner: &str) -> Lock {
+        Self::repository().restore_lock(name, owner)
+    }
+
+    /// Determine if a cache store has been configured
+    pub fn is_initialized() -> bool {
+        CACHE_STORE.get().is_some() || fake::current().is_some()
+    }
+
     /// Retrieve an item from the cache
     pub async fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
-        let store = Self::store();
-        let store = store.read().await;
-        store.get(key).await.and_then(|v| serde_json::from_value(v).ok())
+        Self::repository().get(key).await
     }
 
     /// Store an item in the cache for a given number of seconds
     pub async fn put<T: Serialize>(key: &str, value: T, ttl: Duration) -> bool {
-        let store = Self::store();
-        let store = store.read().await;
-        if let Ok(value) = serde_json::to_value(value) {
-            store.put(key, value, Some(ttl)).await
-        } else {
-            false
-        }
+        Self::repository().put(key, value, ttl).await
     }
 
     /// Store an item in the cache forever
     pub async fn forever<T: Serialize>(key: &str, value: T) -> bool {
-        let store = Self::store();
-        let store = store.read().await;
-        if let Ok(value) = serde_json::to_value(value) {
-            store.put(key, value, None).await
-        } else {
-            false
-        }
+        Self::repository().forever(key, value).await
     }
 
     /// Remove an item from the cache
     pub async fn forget(key: &str) -> bool {
-        let store = Self::store();
-        let store = store.read().await;
-        store.forget(key).await
+        Self::repository().forget(key).await
     }
 
     /// Remove all items from the cache
     pub async fn flush() -> bool {
-        let store = Self::store();
-        let store = store.read().await;
-        store.flush().await
+        Self::repository().flush().await
+    }
+
+    /// Delete expired items from stores that don't remove them on their own
+    pub async fn prune() -> u64 {
+        Self::repository().prune().await
     }
 
     /// Determine if an item exists in the cache
     pub async fn has(key: &str) -> bool {
-        let store = Self::store();
-        let store = store.read().await;
-        store.has(key).await
+        Self::repository().has(key).await
     }
 
     /// Increment the value of an item in the cache
     pub async fn increment(key: &str, value: i64) -> i64 {
-        let store = Self::store();
-        let store = store.read().await;
-        store.increment(key, value).await
+        Self::repository().increment(key, value).await
     }
 
     /// Decrement the value of an item in the cache
     pub async fn decrement(key: &str, value: i64) -> i64 {
-        let store = Self::store();
-        let store = store.read().await;
-        store.decrement(key, value).await
+        Self::repository().decrement(key, value).await
     }
 
     /// Get an item from the cache, or store the default value with a TTL
@@ -117,57 +204,54 @@ impl Cache {
         T: DeserializeOwned + Serialize,
         F: FnOnce() -> T + Send + Sync,
     {
-        if let Some(value) = Self::get(key).await {
-            return Some(value);
-        }
+        Self::repository().remember(key, ttl, callback).await
+    }
 
-        let value = callback();
-        if Self::put(key, &value, ttl).await {
-            Some(value)
-        } else {
-            None
-        }
+    /// Get an item from the cache, or store the value of an async callback with a TTL
+    pub async fn remember_async<T, F, Fut>(key: &str, ttl: Duration, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        Self::repository().remember_async(key, ttl, callback).await
+    }
+
+    /// Get an item from the cache, or store the value of a fallible async callback with a TTL.
+    /// Errors from the callback are returned and nothing is cached.
+    pub async fn try_remember<T, E, F, Fut>(key: &str, ttl: Duration, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        Self::repository().try_remember(key, ttl, callback).await
+    }
+
+    /// Like `try_remember`, but stores the value forever
+    pub async fn try_remember_forever<T, E, F, Fut>(key: &str, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        Self::repository().try_remember_forever(key, callback).await
     }
 
     /// Get an item from the cache with stale-while-revalidate behavior.
-    /// Returns the cached value (even if stale) while asynchronously revalidating it.
+    ///
+    /// A value younger than `ttl` is returned as is. During the following `grace` period the
+    /// stale value is still returned, while a single background task refreshes it. After
+    /// that the value has expired and the callback runs before returning.
     pub async fn flexible<T, F, Fut>(key: &str, ttl: Duration, grace: Duration, callback: F) -> Option<T>
     where
         T: DeserializeOwned + Serialize + Clone + Send + 'static,
         F: FnOnce() -> Fut + Send + Sync + Clone + 'static,
         Fut: Future<Output = T> + Send + 'static,
     {
-        let store = Self::store();
-        let store_read = store.read().await;
-        
-        // Try to get the value from cache
-        if let Some(value) = store_read.get(key).await {
-            // If we have a value, spawn a background task to refresh it if it's stale
-            let key = key.to_string();
-            let callback = callback.clone();
-            let store = store.clone();
-            
-            tokio::spawn(async move {
-                // Check if the value is stale and needs revalidation
-                if let Some(_) = store.read().await.get(&key).await {
-                    // If we have a value, check if it's stale
-                    let new_value = callback().await;
-                    if let Ok(value) = serde_json::to_value(new_value) {
-                        let _ = store.read().await.put(&key, value, Some(ttl)).await;
-                    }
-                }
-            });
-
-            // Return the current value (might be stale) immediately
-            return serde_json::from_value(value).ok();
-        }
-
-        // If no value exists, generate it synchronously
-        let value = callback().await;
-        if let Ok(json_value) = serde_json::to_value(value.clone()) {
-            let _ = store_read.put(key, json_value, Some(ttl + grace)).await;
-        }
-        Some(value)
+        Self::repository().flexible(key, ttl, grace, callback).await
     }
 
     /// Get an item from the cache, or store the default value forever
@@ -176,16 +260,7 @@ impl Cache {
         T: DeserializeOwned + Serialize,
         F: FnOnce() -> T + Send + Sync,
     {
-        if let Some(value) = Self::get(key).await {
-            return Some(value);
-        }
-
-        let value = callback();
-        if Self::forever(key, &value).await {
-            Some(value)
-        } else {
-            None
-        }
+        Self::repository().remember_forever(key, callback).await
     }
 
     /// Get an item from the cache or store the default value
@@ -194,24 +269,16 @@ impl Cache {
         T: DeserializeOwned + Serialize,
         F: FnOnce() -> T + Send + Sync,
     {
-        Self::remember_forever(key, callback).await
+        Self::repository().sear(key, callback).await
     }
 
     /// Pull an item from the cache and delete it
     pub async fn pull<T: DeserializeOwned>(key: &str) -> Option<T> {
-        let value = Self::get(key).await;
-        if value.is_some() {
-            Self::forget(key).await;
-        }
-        value
+        Self::repository().pull(key).await
     }
 
     /// Store an item in the cache if the key doesn't exist
     pub async fn add<T: Serialize>(key: &str, value: T, ttl: Duration) -> bool {
-        if Self::has(key).await {
-            false
-        } else {
-            Self::put(key, value, ttl).await
-        }
+        Self::repository().add(key, value, ttl).await
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,289 @@
+use crate::framework::cache::{flexible, CacheError, CacheStore, Lock, TaggedCache};
+use serde::{de::DeserializeOwned, Serialize};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+type SharedStore = Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>;
+
+/// The cache operations for a single store. The `Cache` facade uses the default store,
+/// and `Cache::store_named` gives a repository for one of the named stores.
+#[derive(Clone)]
+pub struct Repository {
+    store: SharedStore,
+}
+
+impl Repository {
+    pub fn new(store: SharedStore) -> Self {
+        Self { store }
+    }
+
+    /// Get the underlying cache store
+    pub fn store(&self) -> SharedStore {
+        Arc::clone(&self.store)
+    }
+
+    /// Scope cache operations to a set of tags
+    pub fn tags(&self, tags: &[&str]) -> TaggedCache {
+        TaggedCache::new(self.store(), tags)
+    }
+
+    /// Get an atomic lock; a zero `ttl` holds the lock until it's released
+    pub fn lock(&self, name: &str, ttl: Duration) -> Lock {
+        let ttl = (!ttl.is_zero()).then_some(ttl);
+        Lock::new(self.store(), name, ttl, None)
+    }
+
+    /// Get a lock acquired elsewhere by its owner token
+    pub fn restore_lock(&self, name: &str, owner: &str) -> Lock {
+        Lock::new(self.store(), name, None, Some(owner.to_string()))
+    }
+
+    /// Retrieve an item from the cache
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let store = self.store.read().await;
+        store.get(key).await.and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    /// Store an item in the cache for a given number of seconds
+    pub async fn put<T: Serialize>(&self, key: &str, value: T, ttl: Duration) -> bool {
+        let store = self.store.read().await;
+        if let Ok(value) = serde_json::to_value(value) {
+            store.put(key, value, Some(ttl)).await
+        } else {
+            false
+        }
+    }
+
+    /// Store an item in the cache forever
+    pub async fn forever<T: Serialize>(&self, key: &str, value: T) -> bool {
+        let store = self.store.read().await;
+        if let Ok(value) = serde_json::to_value(value) {
+            store.put(key, value, None).await
+        } else {
+            false
+        }
+    }
+
+    /// Remove an item from the cache
+    pub async fn forget(&self, key: &str) -> bool {
+        self.store.read().await.forget(key).await
+    }
+
+    /// Remove all items from the cache
+    pub async fn flush(&self) -> bool {
+        self.store.read().await.flush().await
+    }
+
+    /// Delete expired items from stores that don't remove them on their own
+    pub async fn prune(&self) -> u64 {
+        self.store.read().await.prune().await
+    }
+
+    /// Determine if an item exists in the cache
+    pub async fn has(&self, key: &str) -> bool {
+        self.store.read().await.has(key).await
+    }
+
+    /// Increment the value of an item in the cache
+    pub async fn increment(&self, key: &str, value: i64) -> i64 {
+        self.store.read().await.increment(key, value).await
+    }
+
+    /// Decrement the value of an item in the cache
+    pub async fn decrement(&self, key: &str, value: i64) -> i64 {
+        self.store.read().await.decrement(key, value).await
+    }
+
+    /// Get an item from the cache, or store the default value with a TTL
+    pub async fn remember<T, F>(&self, key: &str, ttl: Duration, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T + Send + Sync,
+    {
+        if let Some(value) = self.get(key).await {
+            return Some(value);
+        }
+
+        let value = callback();
+        if self.put(key, &value, ttl).await {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Get an item from the cache, or store the value of an async callback with a TTL
+    pub async fn remember_async<T, F, Fut>(&self, key: &str, ttl: Duration, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.try_remember(key, ttl, || async { Ok::<_, Infallible>(callback().await) })
+            .await
+    }
+
+    /// Get an item from the cache, or store the value of a fallible async callback with a TTL.
+    /// Errors from the callback are returned and nothing is cached.
+    pub async fn try_remember<T, E, F, Fut>(&self, key: &str, ttl: Duration, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.remember_with(key, Some(ttl), callback).await
+    }
+
+    /// Like `try_remember`, but stores the value forever
+    pub async fn try_remember_forever<T, E, F, Fut>(&self, key: &str, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.remember_with(key, None, callback).await
+    }
+
+    async fn remember_with<T, E, F, Fut>(&self, key: &str, ttl: Option<Duration>, callback: F) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + Serialize,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(key).await {
+            return Ok(value);
+        }
+
+        let value = callback().await.map_err(|e| CacheError::Callback {
+            key: key.to_string(),
+            source: e.into(),
+        })?;
+        let json = serde_json::to_value(&value).map_err(|source| CacheError::Serialization {
+            key: key.to_string(),
+            source,
+        })?;
+        if self.store.read().await.put(key, json, ttl).await {
+            Ok(value)
+        } else {
+            Err(CacheError::WriteFailed(key.to_string()))
+        }
+    }
+
+    /// Get an item from the cache with stale-while-revalidate behavior.
+    ///
+    /// A value younger than `ttl` is returned as is. During the following `grace` period the
+    /// stale value is still returned, while a single background task refreshes it. After
+    /// that the value has expired and the callback runs before returning. The value is stored
+    /// together with its creation time, so read it back with `flexible` rather than `get`.
+    pub async fn flexible<T, F, Fut>(&self, key: &str, ttl: Duration, grace: Duration, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize + Clone + Send + 'static,
+        F: FnOnce() -> Fut + Send + Sync + Clone + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let store = self.store();
+        let cached = flexible::get(&store, key, ttl).await;
+
+        if let Some((value, fresh)) = cached {
+            if !fresh {
+                // Only the caller that takes the lock refreshes, everyone else gets the stale value
+                let lock = flexible::refresh_lock(&store, key);
+                if lock.get().await {
+                    let key = key.to_string();
+                    let store = store.clone();
+                    tokio::spawn(async move {
+                        // Another refresh may have finished between the read and taking the lock
+                        if !flexible::is_fresh(&store, &key, ttl).await {
+                            if let Ok(value) = serde_json::to_value(callback().await) {
+                                flexible::store(&store, &key, value, ttl, grace).await;
+                            }
+                        }
+                        lock.release().await;
+                    });
+                }
+            }
+            return serde_json::from_value(value).ok();
+        }
+
+        // If no value exists, generate it synchronously
+        let value = callback().await;
+        if let Ok(json_value) = serde_json::to_value(value.clone()) {
+            flexible::store(&store, key, json_value, ttl, grace).await;
+        }
+        Some(value)
+    }
+
+    /// Get an item from the cache, or store the default value forever
+    pub async fn remember_forever<T, F>(&self, key: &str, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T + Send + Sync,
+    {
+        if let Some(value) = self.get(key).await {
+            return Some(value);
+        }
+
+        let value = callback();
+        if self.forever(key, &value).await {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Get an item from the cache or store the default value
+    pub async fn sear<T, F>(&self, key: &str, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T + Send + Sync,
+    {
+        self.remember_forever(key, callback).await
+    }
+
+    /// Pull an item from the cache and delete it
+    pub async fn pull<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.get(key).await;
+        if value.is_some() {
+            self.forget(key).await;
+        }
+        value
+    }
+
+    /// Store an item in the cache if the key doesn't exist
+    pub async fn add<T: Serialize>(&self, key: &str, value: T, ttl: Duration) -> bool {
+        let store = self.store.read().await;
+        match serde_json::to_value(value) {
+            Ok(value) => store.add(key, value, Some(ttl)).await,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::cache::drivers::MemoryStore;
+
+    #[tokio::test]
+    async fn test_try_remember_does_not_cache_failures() {
+        let cache = Repository::new(Arc::new(RwLock::new(Box::new(MemoryStore::new()))));
+        let ttl = Duration::from_secs(60);
+
+        let failed = cache
+            .try_remember("report", ttl, || async { Err::<u32, _>("database is down") })
+            .await;
+        assert!(matches!(failed, Err(CacheError::Callback { .. })));
+        assert!(!cache.has("report").await);
+
+        let report = cache.try_remember("report", ttl, || async { Ok::<_, CacheError>(7) }).await;
+        assert_eq!(report.unwrap(), 7);
+        let cached = cache.remember_async("report", ttl, || async { 8 }).await;
+        assert_eq!(cached.unwrap(), 7);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::framework::cache::CacheStore;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+type SharedStore = Arc<RwLock<Box<dyn CacheStore + Send + Sync>>>;
+
+/// A view of the cache where every key is scoped to a set of tags.
+///
+/// Each tag has a version stored in the cache, and tagged keys include the versions of
+/// their tags. Flushing a tag gives it a new version, so everything stored under the old
+/// one can no longer be reached. Stores that track tag members also delete those items.
+pub struct TaggedCache {
+    store: SharedStore,
+    tags: Vec<String>,
+}
+
+impl TaggedCache {
+    pub fn new(store: SharedStore, tags: &[&str]) -> Self {
+        Self {
+            store,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn version_key(tag: &str) -> String {
+        format!("tag:{}:version", tag)
+    }
+
+    /// Get the current version of a tag, creating one if it has none
+    async fn version(store: &(dyn CacheStore + Send + Sync), tag: &str) -> String {
+        let key = Self::version_key(tag);
+        if let Some(Value::String(version)) = store.get(&key).await {
+            return version;
+        }
+
+        // Another caller may create the version at the same time, so read back the winner
+        let version = Uuid::new_v4().to_string();
+        if store.add(&key, Value::from(version.clone()), None).await {
+            return version;
+        }
+        match store.get(&key).await {
+            Some(Value::String(winner)) => winner,
+            _ => version,
+        }
+    }
+
+    /// The key an item is stored under, made of the current versions of the tags
+    async fn tagged_key(&self, key: &str) -> String {
+        let store = self.store.read().await;
+        let mut hasher = Sha256::new();
+        for tag in &self.tags {
+            hasher.update(Self::version(store.as_ref(), tag).await);
+            hasher.update("|");
+        }
+        format!("{}:{}", hex::encode(hasher.finalize()), key)
+    }
+
+    /// Retrieve an item from the tagged cache
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let key = self.tagged_key(key).await;
+        let value = self.store.read().await.get(&key).await?;
+        serde_json::from_value(value).ok()
+    }
+
+    /// Store an item in the tagged cache for the given duration
+    pub async fn put<T: Serialize>(&self, key: &str, value: T, ttl: Duration) -> bool {
+        self.store(key, value, Some(ttl)).await
+    }
+
+    /// Store an item in the tagged cache forever
+    pub async fn forever<T: Serialize>(&self, key: &str, value: T) -> bool {
+        self.store(key, value, None).await
+    }
+
+    async fn store<T: Serialize>(&self, key: &str, value: T, ttl: Option<Duration>) -> bool {
+        let Ok(value) = serde_json::to_value(value) else {
+            return false;
+        };
+        let key = self.tagged_key(key).await;
+
+        let store = self.store.read().await;
+        if !store.put(&key, value, ttl).await {
+            return false;
+        }
+        for tag in &self.tags {
+            store.add_to_tag(tag, &key, ttl).await;
+        }
+        true
+    }
+
+    /// Determine if an item exists in the tagged cache
+    pub async fn has(&self, key: &str) -> bool {
+        let key = self.tagged_key(key).await;
+        self.store.read().await.has(&key).await
+    }
+
+    /// Remove an item from the tagged cache
+    pub async fn forget(&self, key: &str) -> bool {
+        let key = self.tagged_key(key).await;
+        self.store.read().await.forget(&key).await
+    }
+
+    /// Get an item from the tagged cache, or store the default value with a TTL
+    pub async fn remember<T, F>(&self, key: &str, ttl: Duration, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T + Send + Sync,
+    {
+        if let Some(value) = self.get(key).await {
+            return Some(value);
+        }
+
+        let value = callback();
+        if self.put(key, &value, ttl).await {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Get an item from the tagged cache, or store the default value forever
+    pub async fn remember_forever<T, F>(&self, key: &str, callback: F) -> Option<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T + Send + Sync,
+    {
+        if let Some(value) = self.get(key).await {
+            return Some(value);
+        }
+
+        let value = callback();
+        if self.forever(key, &value).await {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Invalidate every item stored under any of the tags
+    pub async fn flush(&self) -> bool {
+        let store = self.store.read().await;
+        for tag in &self.tags {
+            store.flush_tag(tag).await;
+            // The next read creates a new version, so keys built from the old one are unreachable
+            store.forget(&Self::version_key(tag)).await;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::cache::drivers::database::{DatabaseStore, Entity};
+    use crate::framework::testing::{create_table, sqlite_database};
+
+    async fn store() -> SharedStore {
+        let db = sqlite_database("sqlite::memory:").await;
+        create_table(&db, Entity).await;
+        Arc::new(RwLock::new(Box::new(DatabaseStore::new(db))))
+    }
+
+    #[tokio::test]
+    async fn test_flushing_a_tag_invalidates_its_keys() {
+        let store = store().await;
+        let users = TaggedCache::new(store.clone(), &["users"]);
+        let both = TaggedCache::new(store.clone(), &["users", "posts"]);
+        let posts = TaggedCache::new(store.clone(), &["posts"]);
+
+        assert!(users.put("all", vec!["jane"], Duration::from_secs(60)).await);
+        assert!(both.forever("feed", "latest").await);
+        assert!(posts.forever("all", vec!["hello"]).await);
+        assert_eq!(users.get::<Vec<String>>("all").await, Some(vec!["jane".to_string()]));
+        assert!(store.read().await.get("all").await.is_none());
+
+        assert!(users.flush().await);
+        assert_eq!(users.get::<Vec<String>>("all").await, None);
+        assert_eq!(both.get::<String>("feed").await, None);
+        assert_eq!(posts.get::<Vec<String>>("all").await, Some(vec!["hello".to_string()]));
+
+        let remembered = users.remember("all", Duration::from_secs(60), || vec!["john".to_string()]).await;
+        assert_eq!(remembered, Some(vec!["john".to_string()]));
+    }
+}
@@ -0,0 +1,168 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use once_cell::sync::OnceCell;
+use std::env;
+use thiserror::Error;
+
+/// Length of an AES-256 key in bytes
+const KEY_LENGTH: usize = 32;
+
+/// Length of an AES-GCM nonce in bytes
+const NONCE_LENGTH: usize = 12;
+
+static ENCRYPTER: OnceCell<Encrypter> = OnceCell::new();
+
+#[derive(Debug, Error)]
+pub enum CryptError {
+    #[error("No application encryption key has been specified, set APP_KEY")]
+    MissingKey,
+    #[error("Invalid encryption key: {0}")]
+    InvalidKey(String),
+    #[error("Could not encrypt the data")]
+    EncryptionFailed,
+    #[error("The payload is invalid or was encrypted with an unknown key")]
+    DecryptionFailed,
+}
+
+/// Encrypts data with AES-256-GCM, decrypting with previous keys as well so keys can be rotated
+#[derive(Clone)]
+pub struct Encrypter {
+    cipher: Aes256Gcm,
+    previous: Vec<Aes256Gcm>,
+}
+
+impl Encrypter {
+    /// Create an encrypter from a key in the `base64:...` format produced by `generate_key`
+    pub fn new(key: &str) -> Result<Self, CryptError> {
+        Ok(Self {
+            cipher: cipher(key)?,
+            previous: Vec::new(),
+        })
+    }
+
+    /// Keys that were used before the current one, tried in order when decrypting
+    pub fn previous_keys<I, S>(mut self, keys: I) -> Result<Self, CryptError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.previous = keys
+            .into_iter()
+            .map(|key| cipher(key.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Create an encrypter from `APP_KEY` and the comma-separated `APP_PREVIOUS_KEYS`
+    pub fn from_env() -> Result<Self, CryptError> {
+        let key = env::var("APP_KEY").ok().filter(|key| !key.is_empty()).ok_or(CryptError::MissingKey)?;
+        let previous = env::var("APP_PREVIOUS_KEYS").unwrap_or_default();
+        Self::new(&key)?.previous_keys(previous.split(',').map(str::trim).filter(|key| !key.is_empty()))
+    }
+
+    /// Generate a random key in the format expected by `new`
+    pub fn generate_key() -> String {
+        format!("base64:{}", STANDARD.encode(Aes256Gcm::generate_key(OsRng)))
+    }
+
+    /// Encrypt bytes into a base64 string holding the nonce and the ciphertext
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, CryptError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| CryptError::EncryptionFailed)?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(payload))
+    }
+
+    /// Decrypt a payload created by `encrypt` with the current or one of the previous keys
+    pub fn decrypt(&self, payload: &str) -> Result<Vec<u8>, CryptError> {
+        let payload = STANDARD.decode(payload).map_err(|_| CryptError::DecryptionFailed)?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(CryptError::DecryptionFailed);
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let nonce = Nonce::from_slice(nonce);
+
+        std::iter::once(&self.cipher)
+            .chain(&self.previous)
+            .find_map(|cipher| cipher.decrypt(nonce, ciphertext).ok())
+            .ok_or(CryptError::DecryptionFailed)
+    }
+
+    pub fn encrypt_string(&self, value: &str) -> Result<String, CryptError> {
+        self.encrypt(value.as_bytes())
+    }
+
+    pub fn decrypt_string(&self, payload: &str) -> Result<String, CryptError> {
+        String::from_utf8(self.decrypt(payload)?).map_err(|_| CryptError::DecryptionFailed)
+    }
+}
+
+fn cipher(key: &str) -> Result<Aes256Gcm, CryptError> {
+    let encoded = key.strip_prefix("base64:").unwrap_or(key);
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|e| CryptError::InvalidKey(e.to_string()))?;
+    if bytes.len() != KEY_LENGTH {
+        return Err(CryptError::InvalidKey(format!(
+            "expected {} bytes, got {}",
+            KEY_LENGTH,
+            bytes.len()
+        )));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)))
+}
+
+/// A Laravel-like facade for encrypting values with the application key
+pub struct Crypt;
+
+impl Crypt {
+    /// Use the given encrypter instead of one built from the environment.
+    /// Must be called before anything is encrypted.
+    pub fn set_encrypter(encrypter: Encrypter) -> Result<(), CryptError> {
+        ENCRYPTER
+            .set(encrypter)
+            .map_err(|_| CryptError::InvalidKey("the encrypter is already initialized".to_string()))
+    }
+
+    /// Get the application encrypter, created from the environment on first use
+    pub fn encrypter() -> Result<&'static Encrypter, CryptError> {
+        ENCRYPTER.get_or_try_init(Encrypter::from_env)
+    }
+
+    pub fn encrypt_string(value: &str) -> Result<String, CryptError> {
+        Self::encrypter()?.encrypt_string(value)
+    }
+
+    pub fn decrypt_string(payload: &str) -> Result<String, CryptError> {
+        Self::encrypter()?.decrypt_string(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypts_with_previous_keys() {
+        let old_key = Encrypter::generate_key();
+        let new_key = Encrypter::generate_key();
+
+        let old = Encrypter::new(&old_key).unwrap();
+        let encrypted = old.encrypt_string("secret").unwrap();
+        assert_ne!(encrypted, old.encrypt_string("secret").unwrap());
+        assert_eq!(old.decrypt_string(&encrypted).unwrap(), "secret");
+
+        let rotated = Encrypter::new(&new_key).unwrap().previous_keys([&old_key]).unwrap();
+        assert_eq!(rotated.decrypt_string(&encrypted).unwrap(), "secret");
+        assert!(Encrypter::new(&new_key).unwrap().decrypt_string(&encrypted).is_err());
+
+        assert!(Encrypter::new("base64:c2hvcnQ=").is_err());
+    }
+}
@@ -15,6 +15,8 @@ pub mod schedule;
 pub mod queue;
 pub mod testing;
 pub mod http;
+pub mod aws;
+pub mod crypt;
 
 // Re-export framework types
 pub use middleware::{
@@ -37,7 +39,7 @@ pub use prelude::*;
 
 // Re-export storage and cache functionality
 pub use storage::Storage;
-pub use storage::config::{StorageConfig, LocalDiskConfig, init_storage};
+pub use storage::config::{StorageConfig, DiskConfig, LocalDiskConfig, S3DiskConfig, init_storage};
 pub use cache::Cache;
 pub use cache::config::{CacheConfig, CacheDriver, init_cache};
 
@@ -0,0 +1,521 @@
+use crate::framework::queue::job::{Dispatchable, PendingJob};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+static BATCH_REPOSITORY: OnceCell<Arc<dyn BatchRepository + Send + Sync>> = OnceCell::new();
+
+/// The callbacks and settings stored alongside a batch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchOptions {
+    #[serde(default)]
+    pub then: Option<PendingJob>,
+    #[serde(default)]
+    pub catch: Option<PendingJob>,
+    #[serde(default)]
+    pub finally: Option<PendingJob>,
+    #[serde(default)]
+    pub allow_failures: bool,
+}
+
+/// The persisted state of a group of jobs dispatched together
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub id: Uuid,
+    pub name: String,
+    pub total_jobs: u32,
+    pub pending_jobs: u32,
+    pub failed_jobs: u32,
+    /// Jobs that were skipped because the batch was cancelled before they ran
+    pub skipped_jobs: u32,
+    pub failed_job_ids: Vec<Uuid>,
+    pub options: BatchOptions,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Batch {
+    /// The number of jobs that have either succeeded or failed
+    pub fn processed_jobs(&self) -> u32 {
+        self.total_jobs - self.pending_jobs - self.skipped_jobs
+    }
+
+    /// The percentage of jobs that have been processed
+    pub fn progress(&self) -> u32 {
+        if self.total_jobs == 0 {
+            return 100;
+        }
+        self.processed_jobs() * 100 / self.total_jobs
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled_at.is_some()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failed_jobs > 0
+    }
+}
+
+/// Stores batches and keeps their counters up to date as jobs finish
+#[async_trait]
+pub trait BatchRepository {
+    /// Persist a new batch
+    async fn store(&self, batch: &Batch) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Find a batch by its id
+    async fn find(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>>;
+
+    /// Record that one of the batch's jobs succeeded, returning the updated batch
+    async fn record_success(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>>;
+
+    /// Record that one of the batch's jobs failed for good, returning the updated batch
+    async fn record_failure(&self, id: Uuid, job_id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>>;
+
+    /// Record that one of the batch's jobs was skipped because the batch was cancelled,
+    /// returning the updated batch
+    async fn record_skipped(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>>;
+
+    /// Mark a batch as cancelled so its remaining jobs are skipped
+    async fn cancel(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Delete a batch
+    async fn delete(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Set the repository used to store batches
+pub fn set_repository(repository: Arc<dyn BatchRepository + Send + Sync>) {
+    let _ = BATCH_REPOSITORY.set(repository);
+}
+
+/// Get the repository used to store batches
+pub fn repository() -> Result<Arc<dyn BatchRepository + Send + Sync>, Box<dyn std::error::Error>> {
+    BATCH_REPOSITORY
+        .get()
+        .cloned()
+        .ok_or_else(|| "Job batches are not initialized".into())
+}
+
+/// A batch that is being built and hasn't been dispatched yet
+pub struct PendingBatch {
+    id: Uuid,
+    name: String,
+    jobs: Vec<Box<dyn Dispatchable>>,
+    then: Option<Box<dyn Dispatchable>>,
+    catch: Option<Box<dyn Dispatchable>>,
+    finally: Option<Box<dyn Dispatchable>>,
+    allow_failures: bool,
+}
+
+impl PendingBatch {
+    pub fn new(jobs: Vec<Box<dyn Dispatchable>>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: String::new(),
+            jobs,
+            then: None,
+            catch: None,
+            finally: None,
+            allow_failures: false,
+        }
+    }
+
+    /// The id the batch will be stored under, so callback jobs can refer to it
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Dispatch a job once every job in the batch has succeeded
+    pub fn then(mut self, job: impl Dispatchable + 'static) -> Self {
+        self.then = Some(Box::new(job));
+        self
+    }
+
+    /// Dispatch a job when the first job in the batch fails
+    pub fn catch(mut self, job: impl Dispatchable + 'static) -> Self {
+        self.catch = Some(Box::new(job));
+        self
+    }
+
+    /// Dispatch a job once the batch has finished, whether or not its jobs succeeded
+    pub fn finally(mut self, job: impl Dispatchable + 'static) -> Self {
+        self.finally = Some(Box::new(job));
+        self
+    }
+
+    /// Keep running the batch after a job fails instead of cancelling it
+    pub fn allow_failures(mut self) -> Self {
+        self.allow_failures = true;
+        self
+    }
+
+    /// Store the batch and push its jobs
+    pub async fn dispatch(self) -> Result<Batch, Box<dyn std::error::Error>> {
+        let pending = |job: &Option<Box<dyn Dispatchable>>| job.as_ref().map(|job| job.pending()).transpose();
+        let options = BatchOptions {
+            then: pending(&self.then)?,
+            catch: pending(&self.catch)?,
+            finally: pending(&self.finally)?,
+            allow_failures: self.allow_failures,
+        };
+
+        let jobs = self
+            .jobs
+            .iter()
+            .map(|job| job.pending())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let batch = Batch {
+            id: self.id,
+            name: self.name,
+            total_jobs: jobs.len() as u32,
+            pending_jobs: jobs.len() as u32,
+            failed_jobs: 0,
+            skipped_jobs: 0,
+            failed_job_ids: Vec::new(),
+            options,
+            cancelled_at: None,
+            created_at: Utc::now(),
+            // An empty batch has nothing to wait for
+            finished_at: jobs.is_empty().then(Utc::now),
+        };
+        repository()?.store(&batch).await?;
+
+        if batch.finished() {
+            finished(&batch).await;
+        }
+        for mut job in jobs {
+            job.job.batch_id = Some(batch.id);
+            job.push().await?;
+        }
+        Ok(batch)
+    }
+}
+
+/// Update a batch after one of its jobs succeeded, dispatching callbacks if it just finished
+pub(crate) async fn job_succeeded(batch_id: Uuid) {
+    // Errors are turned into strings so the future stays Send for the sync queue driver
+    let batch = match repository().map_err(|e| e.to_string()) {
+        Ok(repository) => repository.record_success(batch_id).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    match batch {
+        Ok(Some(batch)) if batch.pending_jobs == 0 => finished(&batch).await,
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to update batch {}: {}", batch_id, e),
+    }
+}
+
+/// Update a batch after one of its jobs was skipped, dispatching callbacks if it just finished
+pub(crate) async fn job_skipped(batch_id: Uuid) {
+    let batch = match repository().map_err(|e| e.to_string()) {
+        Ok(repository) => repository.record_skipped(batch_id).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    match batch {
+        Ok(Some(batch)) if batch.pending_jobs == 0 => finished(&batch).await,
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to update batch {}: {}", batch_id, e),
+    }
+}
+
+/// Update a batch after one of its jobs failed for good, dispatching callbacks as needed
+pub(crate) async fn job_failed(batch_id: Uuid, job_id: Uuid) {
+    let repository = match repository().map_err(|e| e.to_string()) {
+        Ok(repository) => repository,
+        Err(e) => {
+            eprintln!("Failed to update batch {}: {}", batch_id, e);
+            return;
+        }
+    };
+
+    let batch = match repository.record_failure(batch_id, job_id).await {
+        Ok(Some(batch)) => batch,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Failed to update batch {}: {}", batch_id, e);
+            return;
+        }
+    };
+
+    if batch.failed_jobs == 1 {
+        if !batch.options.allow_failures {
+            if let Err(e) = repository.cancel(batch_id).await {
+                eprintln!("Failed to cancel batch {}: {}", batch_id, e);
+            }
+        }
+        dispatch_callback(&batch, &batch.options.catch).await;
+    }
+    if batch.pending_jobs == 0 {
+        finished(&batch).await;
+    }
+}
+
+/// Whether the jobs of a batch should be skipped because it was cancelled
+pub(crate) async fn is_cancelled(batch_id: Uuid) -> bool {
+    match repository().ok() {
+        Some(repository) => matches!(repository.find(batch_id).await, Ok(Some(batch)) if batch.cancelled()),
+        None => false,
+    }
+}
+
+async fn finished(batch: &Batch) {
+    if !batch.has_failures() && !batch.cancelled() {
+        dispatch_callback(batch, &batch.options.then).await;
+    }
+    dispatch_callback(batch, &batch.options.finally).await;
+}
+
+async fn dispatch_callback(batch: &Batch, callback: &Option<PendingJob>) {
+    if let Some(job) = callback.clone() {
+        if let Err(e) = job.push().await {
+            eprintln!("Failed to dispatch callback for batch {}: {}", batch.id, e);
+        }
+    }
+}
+
+/// Entity for the `job_batches` table
+pub mod job_batch {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "job_batches")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: Uuid,
+        pub name: String,
+        pub total_jobs: i32,
+        pub pending_jobs: i32,
+        pub failed_jobs: i32,
+        pub skipped_jobs: i32,
+        #[sea_orm(column_type = "Text")]
+        pub failed_job_ids: String,
+        #[sea_orm(column_type = "Text")]
+        pub options: String,
+        pub cancelled_at: Option<DateTimeWithTimeZone>,
+        pub created_at: DateTimeWithTimeZone,
+        pub finished_at: Option<DateTimeWithTimeZone>,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+impl TryFrom<job_batch::Model> for Batch {
+    type Error = serde_json::Error;
+
+    fn try_from(model: job_batch::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: model.id,
+            name: model.name,
+            total_jobs: model.total_jobs.max(0) as u32,
+            pending_jobs: model.pending_jobs.max(0) as u32,
+            failed_jobs: model.failed_jobs.max(0) as u32,
+            skipped_jobs: model.skipped_jobs.max(0) as u32,
+            failed_job_ids: serde_json::from_str(&model.failed_job_ids)?,
+            options: serde_json::from_str(&model.options)?,
+            cancelled_at: model.cancelled_at.map(Into::into),
+            created_at: model.created_at.into(),
+            finished_at: model.finished_at.map(Into::into),
+        })
+    }
+}
+
+/// How a job of a batch finished
+enum Outcome {
+    Succeeded,
+    Failed(Uuid),
+    Skipped,
+}
+
+/// Stores batches in the `job_batches` table
+pub struct DatabaseBatchRepository {
+    db: DatabaseConnection,
+}
+
+impl DatabaseBatchRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Apply a counter update and read the batch back in one transaction. The update locks the
+    /// row, so exactly one caller sees the batch reach zero pending jobs.
+    async fn update_counters(&self, id: Uuid, outcome: Outcome) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin().await?;
+
+        let mut update = job_batch::Entity::update_many()
+            .col_expr(job_batch::Column::PendingJobs, Expr::col(job_batch::Column::PendingJobs).sub(1))
+            .filter(job_batch::Column::Id.eq(id))
+            .filter(job_batch::Column::PendingJobs.gt(0));
+        match outcome {
+            Outcome::Succeeded => {}
+            Outcome::Failed(_) => {
+                update = update.col_expr(job_batch::Column::FailedJobs, Expr::col(job_batch::Column::FailedJobs).add(1));
+            }
+            Outcome::Skipped => {
+                update = update.col_expr(job_batch::Column::SkippedJobs, Expr::col(job_batch::Column::SkippedJobs).add(1));
+            }
+        }
+        if update.exec(&txn).await?.rows_affected == 0 {
+            txn.commit().await?;
+            return Ok(None);
+        }
+
+        let model = match job_batch::Entity::find_by_id(id).one(&txn).await? {
+            Some(model) => model,
+            None => {
+                txn.commit().await?;
+                return Ok(None);
+            }
+        };
+        let mut batch = Batch::try_from(model.clone())?;
+        let mut active: job_batch::ActiveModel = model.into();
+
+        if let Outcome::Failed(job_id) = outcome {
+            batch.failed_job_ids.push(job_id);
+            active.failed_job_ids = Set(serde_json::to_string(&batch.failed_job_ids)?);
+        }
+        if batch.pending_jobs == 0 {
+            let now = Utc::now();
+            batch.finished_at = Some(now);
+            active.finished_at = Set(Some(now.into()));
+        }
+        active.update(&txn).await?;
+
+        txn.commit().await?;
+        Ok(Some(batch))
+    }
+}
+
+#[async_trait]
+impl BatchRepository for DatabaseBatchRepository {
+    async fn store(&self, batch: &Batch) -> Result<(), Box<dyn std::error::Error>> {
+        let model = job_batch::ActiveModel {
+            id: Set(batch.id),
+            name: Set(batch.name.clone()),
+            total_jobs: Set(batch.total_jobs as i32),
+            pending_jobs: Set(batch.pending_jobs as i32),
+            failed_jobs: Set(batch.failed_jobs as i32),
+            skipped_jobs: Set(batch.skipped_jobs as i32),
+            failed_job_ids: Set(serde_json::to_string(&batch.failed_job_ids)?),
+            options: Set(serde_json::to_string(&batch.options)?),
+            cancelled_at: Set(batch.cancelled_at.map(Into::into)),
+            created_at: Set(batch.created_at.into()),
+            finished_at: Set(batch.finished_at.map(Into::into)),
+        };
+        job_batch::Entity::insert(model).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        match job_batch::Entity::find_by_id(id).one(&self.db).await? {
+            Some(model) => Ok(Some(Batch::try_from(model)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn record_success(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        self.update_counters(id, Outcome::Succeeded).await
+    }
+
+    async fn record_failure(&self, id: Uuid, job_id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        self.update_counters(id, Outcome::Failed(job_id)).await
+    }
+
+    async fn record_skipped(&self, id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        self.update_counters(id, Outcome::Skipped).await
+    }
+
+    async fn cancel(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        job_batch::Entity::update_many()
+            .col_expr(job_batch::Column::CancelledAt, Expr::value(Some(chrono::DateTime::<chrono::FixedOffset>::from(Utc::now()))))
+            .filter(job_batch::Column::Id.eq(id))
+            .filter(job_batch::Column::CancelledAt.is_null())
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        job_batch::Entity::delete_by_id(id).exec(&self.db).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::testing::{create_table, sqlite_database};
+
+    async fn repository() -> DatabaseBatchRepository {
+        let db = sqlite_database("sqlite::memory:").await;
+        create_table(&db, job_batch::Entity).await;
+        DatabaseBatchRepository::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_batch_counters() {
+        let repository = repository().await;
+        let batch = Batch {
+            id: Uuid::new_v4(),
+            name: "import".to_string(),
+            total_jobs: 4,
+            pending_jobs: 4,
+            failed_jobs: 0,
+            skipped_jobs: 0,
+            failed_job_ids: Vec::new(),
+            options: BatchOptions::default(),
+            cancelled_at: None,
+            created_at: Utc::now(),
+            finished_at: None,
+        };
+        repository.store(&batch).await.unwrap();
+
+        let updated = repository.record_success(batch.id).await.unwrap().unwrap();
+        assert_eq!(updated.pending_jobs, 3);
+        assert_eq!(updated.progress(), 25);
+
+        let failed_job = Uuid::new_v4();
+        let updated = repository.record_failure(batch.id, failed_job).await.unwrap().unwrap();
+        assert_eq!(updated.pending_jobs, 2);
+        assert_eq!(updated.failed_jobs, 1);
+        assert_eq!(updated.failed_job_ids, vec![failed_job]);
+        assert!(!updated.finished());
+
+        let updated = repository.record_success(batch.id).await.unwrap().unwrap();
+        assert_eq!(updated.pending_jobs, 1);
+
+        // Skipped jobs let the batch finish without counting as processed
+        let updated = repository.record_skipped(batch.id).await.unwrap().unwrap();
+        assert_eq!((updated.pending_jobs, updated.skipped_jobs), (0, 1));
+        assert_eq!(updated.processed_jobs(), 3);
+        assert_eq!(updated.progress(), 75);
+        assert!(updated.finished());
+
+        // A finished batch can't be counted down any further
+        assert!(repository.record_success(batch.id).await.unwrap().is_none());
+
+        repository.cancel(batch.id).await.unwrap();
+        let found = repository.find(batch.id).await.unwrap().unwrap();
+        assert!(found.cancelled());
+        assert!(found.finished());
+        assert_eq!(found.failed_job_ids, vec![failed_job]);
+    }
+}
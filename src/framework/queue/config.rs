@@ -1,47 +1,133 @@
 use sea_orm::DatabaseConnection;
 use crate::framework::queue::{QueueDriver, QUEUE_DRIVER};
-use crate::framework::queue::drivers::{DatabaseDriver, SqsDriver};
+use crate::framework::queue::batch::{self, DatabaseBatchRepository};
+use crate::framework::queue::drivers::{DatabaseDriver, NullDriver, RedisDriver, SqsDriver, SyncDriver};
+use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone)]
 pub enum QueueDriverType {
     Database,
+    Redis,
     Sqs,
+    /// Run jobs immediately when they are dispatched
+    Sync,
+    /// Discard every job
+    Null,
+}
+
+impl QueueDriverType {
+    /// Parse a driver name such as the `QUEUE_CONNECTION` environment variable
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "database" => Some(Self::Database),
+            "redis" => Some(Self::Redis),
+            "sqs" => Some(Self::Sqs),
+            "sync" => Some(Self::Sync),
+            "null" => Some(Self::Null),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct QueueConfig {
     pub driver: QueueDriverType,
+    pub redis_url: Option<String>,
     pub sqs_region: Option<String>,
     pub sqs_queue_url: Option<String>,
+    /// Override for the SQS endpoint, e.g. a local ElasticMQ instance
+    pub sqs_endpoint: Option<String>,
+    /// Queue that failed SQS jobs are moved to
+    pub sqs_failed_queue_url: Option<String>,
     pub default_queue: String,
+    /// How long a job may stay reserved before it is handed to another worker.
+    /// This should be longer than the longest running job's timeout.
+    pub retry_after: Duration,
 }
 
 impl Default for QueueConfig {
     fn default() -> Self {
         Self {
-            driver: QueueDriverType::Database,
-            sqs_region: None,
-            sqs_queue_url: None,
+            driver: default_driver(),
+            redis_url: env::var("REDIS_URL").ok(),
+            sqs_region: env::var("AWS_DEFAULT_REGION").ok(),
+            sqs_queue_url: env::var("SQS_QUEUE_URL").ok(),
+            sqs_endpoint: env::var("SQS_ENDPOINT").ok(),
+            sqs_failed_queue_url: env::var("SQS_FAILED_QUEUE_URL").ok(),
             default_queue: "default".to_string(),
+            retry_after: default_retry_after(),
         }
     }
 }
 
-pub async fn init_queue(config: QueueConfig, db: DatabaseConnection) -> Result<(), String> {
+impl QueueConfig {
+    /// Whether the driver needs a database connection. Every driver but sync and null also
+    /// tracks job batches in the database.
+    pub fn needs_database(&self) -> bool {
+        !matches!(self.driver, QueueDriverType::Sync | QueueDriverType::Null)
+    }
+}
+
+fn default_retry_after() -> Duration {
+    env::var("QUEUE_RETRY_AFTER")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(90))
+}
+
+fn default_driver() -> QueueDriverType {
+    env::var("QUEUE_CONNECTION")
+        .ok()
+        .and_then(|name| QueueDriverType::from_name(&name))
+        .unwrap_or(QueueDriverType::Database)
+}
+
+/// Initialize the queue driver. A database connection is only required by the database
+/// driver and for job batches.
+pub async fn init_queue(config: QueueConfig, db: Option<DatabaseConnection>) -> Result<(), String> {
     // If queue driver is already initialized, return early
     if QUEUE_DRIVER.get().is_some() {
         return Ok(());
     }
 
+    // Batches are tracked in the database whichever driver holds the jobs
+    if let Some(db) = &db {
+        batch::set_repository(Arc::new(DatabaseBatchRepository::new(db.clone())));
+    }
+
     let driver: Box<dyn QueueDriver + Send + Sync> = match config.driver {
-        QueueDriverType::Database => Box::new(DatabaseDriver::new(db)),
+        QueueDriverType::Database => {
+            let db = db.ok_or("The database queue driver requires a database connection")?;
+            Box::new(DatabaseDriver::new(db).retry_after(config.retry_after))
+        }
+        QueueDriverType::Redis => {
+            let redis_url = config.redis_url.ok_or("Redis URL not configured")?;
+            Box::new(
+                RedisDriver::new(&redis_url)
+                    .map_err(|e| e.to_string())?
+                    .retry_after(config.retry_after),
+            )
+        }
         QueueDriverType::Sqs => {
             let region = config.sqs_region.ok_or("SQS region not configured")?;
             let queue_url = config.sqs_queue_url.ok_or("SQS queue URL not configured")?;
-            Box::new(SqsDriver::new(&region, &queue_url).map_err(|e| e.to_string())?)
+            let mut driver = SqsDriver::new(&region, &queue_url)
+                .map_err(|e| e.to_string())?
+                .retry_after(config.retry_after);
+            if let Some(endpoint) = &config.sqs_endpoint {
+                driver = driver.endpoint(endpoint);
+            }
+            if let Some(failed_queue_url) = &config.sqs_failed_queue_url {
+                driver = driver.failed_queue_url(failed_queue_url);
+            }
+            Box::new(driver)
         }
+        QueueDriverType::Sync => Box::new(SyncDriver::new()),
+        QueueDriverType::Null => Box::new(NullDriver::new()),
     };
 
     QUEUE_DRIVER
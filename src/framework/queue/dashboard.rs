@@ -0,0 +1,175 @@
+use crate::framework::queue::{FailedJob, Queue, QueueStats};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// How many failed jobs the dashboard lists
+const RECENT_FAILURES: usize = 10;
+
+/// Everything shown on the dashboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardStats {
+    pub queues: Vec<QueueStats>,
+    pub recent_failures: Vec<FailedJob>,
+}
+
+impl DashboardStats {
+    /// Collect the stats of the given queues and the most recent failures
+    pub async fn collect(queues: &[String]) -> Result<Self, String> {
+        let mut stats = Vec::new();
+        for queue in queues {
+            stats.push(Queue::stats(queue).await.map_err(|e| e.to_string())?);
+        }
+
+        // Drivers without failed job storage just have nothing to show
+        let mut recent_failures = Queue::failed_jobs().await.unwrap_or_default();
+        recent_failures.sort_by_key(|job| std::cmp::Reverse(job.failed_at));
+        recent_failures.truncate(RECENT_FAILURES);
+
+        Ok(Self { queues: stats, recent_failures })
+    }
+}
+
+/// A router serving the queue dashboard, to be nested under a path of your choosing:
+///
+/// ```ignore
+/// Router::new().nest("/queues", queue::dashboard::routes(&["default", "emails"]))
+/// ```
+///
+/// `GET /` serves an HTML page and `GET /stats` the same data as JSON. The router is not
+/// protected in any way, so put it behind your own auth middleware.
+pub fn routes<S>(queues: &[&str]) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let queues: Arc<Vec<String>> = Arc::new(queues.iter().map(|q| q.to_string()).collect());
+    Router::new()
+        .route("/", get(page))
+        .route("/stats", get(stats))
+        .with_state(queues)
+}
+
+async fn stats(State(queues): State<Arc<Vec<String>>>) -> Response {
+    match DashboardStats::collect(&queues).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn page(State(queues): State<Arc<Vec<String>>>) -> Response {
+    match DashboardStats::collect(&queues).await {
+        Ok(stats) => Html(render(&stats)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render(stats: &DashboardStats) -> String {
+    let queues: String = stats
+        .queues
+        .iter()
+        .map(|q| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td></tr>",
+                escape(&q.queue),
+                q.pending,
+                q.reserved,
+                q.delayed,
+                q.failed,
+                q.oldest_pending_seconds.map(|s| format!("{}s", s)).unwrap_or_else(|| "-".to_string()),
+                q.processed_per_minute,
+                q.failed_per_minute,
+            )
+        })
+        .collect();
+
+    let failures: String = if stats.recent_failures.is_empty() {
+        "<tr><td colspan=\"4\">No failed jobs</td></tr>".to_string()
+    } else {
+        stats
+            .recent_failures
+            .iter()
+            .map(|job| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td><pre>{}</pre></td></tr>",
+                    job.id,
+                    escape(&job.queue),
+                    job.failed_at.format("%Y-%m-%d %H:%M:%S"),
+                    escape(&job.exception),
+                )
+            })
+            .collect()
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="5">
+<title>Queues</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #222; }}
+table {{ border-collapse: collapse; width: 100%; margin-bottom: 2rem; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: .5rem; text-align: left; vertical-align: top; }}
+pre {{ margin: 0; white-space: pre-wrap; }}
+</style>
+</head>
+<body>
+<h1>Queues</h1>
+<table>
+<tr><th>Queue</th><th>Pending</th><th>Reserved</th><th>Delayed</th><th>Failed</th><th>Oldest</th><th>Processed/min</th><th>Failed/min</th></tr>
+{}
+</table>
+<h2>Recent failures</h2>
+<table>
+<tr><th>ID</th><th>Queue</th><th>Failed at</th><th>Exception</th></tr>
+{}
+</table>
+</body>
+</html>"#,
+        queues, failures
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes_failures() {
+        let stats = DashboardStats {
+            queues: vec![QueueStats {
+                queue: "default".to_string(),
+                pending: 3,
+                ..Default::default()
+            }],
+            recent_failures: vec![FailedJob {
+                id: uuid::Uuid::new_v4(),
+                queue: "default".to_string(),
+                payload: "{}".to_string(),
+                exception: "<script>alert(1)</script>".to_string(),
+                failed_at: chrono::Utc::now(),
+            }],
+        };
+
+        let html = render(&stats);
+        assert!(html.contains("<td>default</td><td>3</td>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::framework::queue::{QueueDriver, QueuedJob};
+use crate::framework::queue::{FailedJob, QueueDriver, QueueStats, QueuedJob};
 use async_trait::async_trait;
-use sea_orm::{DatabaseConnection, EntityTrait, Set, ActiveModelTrait, QueryFilter, ColumnTrait, QueryOrder};
+use sea_orm::{DatabaseConnection, EntityTrait, Set, ActiveModelTrait, QueryFilter, ColumnTrait, QueryOrder, TransactionTrait, Condition, PaginatorTrait};
+use sea_orm::sea_query::Expr;
 use std::time::Duration;
 use sea_orm::entity::prelude::*;
 use uuid::Uuid;
@@ -25,20 +26,137 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// Entity for jobs that exhausted their attempts
+pub mod failed_job {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "failed_jobs")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: Uuid,
+        pub queue: String,
+        #[sea_orm(column_type = "Text")]
+        pub payload: String,
+        #[sea_orm(column_type = "Text")]
+        pub exception: String,
+        pub failed_at: DateTimeWithTimeZone,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+impl From<failed_job::Model> for FailedJob {
+    fn from(model: failed_job::Model) -> Self {
+        Self {
+            id: model.id,
+            queue: model.queue,
+            payload: model.payload,
+            exception: model.exception,
+            failed_at: model.failed_at.into(),
+        }
+    }
+}
+
+/// A new job, ready to be inserted
+fn new_job(queue: &str, payload: String, available_at: DateTime<Utc>) -> ActiveModel {
+    ActiveModel {
+        id: Set(Uuid::new_v4()),
+        queue: Set(queue.to_string()),
+        payload: Set(payload),
+        attempts: Set(0),
+        reserved_at: Set(None),
+        available_at: Set(available_at.into()),
+        created_at: Set(Utc::now().into()),
+    }
+}
+
+/// Number of candidates a worker tries to claim before reporting an empty queue
+const RESERVE_ATTEMPTS: usize = 5;
+
 pub struct DatabaseDriver {
     db: DatabaseConnection,
+    retry_after: Duration,
 }
 
 impl DatabaseDriver {
     pub fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+        Self {
+            db,
+            retry_after: Duration::from_secs(90),
+        }
+    }
+
+    /// Set how long a job may stay reserved before another worker may pick it up again
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Find the oldest job that is either available or whose reservation has expired
+    async fn next_candidate(&self, queue: &str, now: DateTime<Utc>) -> Result<Option<Model>, DbErr> {
+        let expired = now - chrono::Duration::from_std(self.retry_after).unwrap_or_else(|_| chrono::Duration::zero());
+        Entity::find()
+            .filter(Column::Queue.eq(queue))
+            .filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(Column::ReservedAt.is_null())
+                            .add(Column::AvailableAt.lte(now)),
+                    )
+                    .add(Column::ReservedAt.lte(expired)),
+            )
+            .order_by_asc(Column::AvailableAt)
+            .order_by_asc(Column::CreatedAt)
+            .one(&self.db)
+            .await
+    }
+
+    /// Reserve the candidate, succeeding only if no other worker claimed it since it was read
+    /// Make a reserved job available again after the delay, adding to its attempts
+    async fn unreserve(&self, id: Uuid, delay: Option<Duration>, attempts: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let available_at: DateTimeWithTimeZone = match delay {
+            Some(delay) => (Utc::now() + chrono::Duration::from_std(delay)?).into(),
+            None => Utc::now().into(),
+        };
+
+        Entity::update_many()
+            .col_expr(Column::ReservedAt, Expr::value(Option::<DateTimeWithTimeZone>::None))
+            .col_expr(Column::AvailableAt, Expr::value(available_at))
+            .col_expr(Column::Attempts, Expr::col(Column::Attempts).add(attempts))
+            .filter(Column::Id.eq(id))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn reserve(&self, candidate: &Model, now: DateTime<Utc>) -> Result<bool, DbErr> {
+        let reserved_at: DateTimeWithTimeZone = now.into();
+        let mut update = Entity::update_many()
+            .col_expr(Column::ReservedAt, Expr::value(reserved_at))
+            .filter(Column::Id.eq(candidate.id));
+
+        update = match candidate.reserved_at {
+            None => update.filter(Column::ReservedAt.is_null()),
+            // The previous reservation expired, so the worker holding it crashed or timed out
+            // and that run counts as an attempt
+            Some(previous) => update
+                .filter(Column::ReservedAt.eq(previous))
+                .col_expr(Column::Attempts, Expr::col(Column::Attempts).add(1)),
+        };
+
+        let result = update.exec(&self.db).await?;
+        Ok(result.rows_affected == 1)
     }
 }
 
 #[async_trait]
 impl QueueDriver for DatabaseDriver {
     async fn push(&self, queue: &str, payload: String, delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4();
         let now = Utc::now();
         let available_at = if let Some(delay) = delay {
             now + chrono::Duration::from_std(delay)?
@@ -46,42 +164,46 @@ impl QueueDriver for DatabaseDriver {
             now
         };
 
-        let model = ActiveModel {
-            id: Set(id),
-            queue: Set(queue.to_string()),
-            payload: Set(payload),
-            attempts: Set(0),
-            reserved_at: Set(None),
-            available_at: Set(available_at.into()),
-            created_at: Set(now.into()),
-        };
-        model.insert(&self.db).await?;
+        let model = new_job(queue, payload, available_at);
+        let id = model.insert(&self.db).await?.id;
         Ok(id)
     }
 
     async fn pop(&self, queue: &str) -> Option<QueuedJob> {
-        let now = Utc::now();
-        if let Ok(result) = Entity::find()
-            .filter(Column::Queue.eq(queue))
-            .filter(Column::ReservedAt.is_null())
-            .filter(Column::AvailableAt.lte(now))
-            .order_by_asc(Column::Id)
-            .one(&self.db)
-            .await {
-            if let Some(job) = result {
-                let mut job: ActiveModel = job.into();
-                job.reserved_at = Set(Some(now.into()));
-                if let Ok(job) = job.update(&self.db).await {
+        for _ in 0..RESERVE_ATTEMPTS {
+            let now = Utc::now();
+            let candidate = match self.next_candidate(queue, now).await {
+                Ok(Some(candidate)) => candidate,
+                Ok(None) => return None,
+                Err(e) => {
+                    eprintln!("Failed to fetch next job from queue {}: {}", queue, e);
+                    return None;
+                }
+            };
+
+            match self.reserve(&candidate, now).await {
+                Ok(true) => {
+                    let attempts = if candidate.reserved_at.is_some() {
+                        candidate.attempts + 1
+                    } else {
+                        candidate.attempts
+                    };
                     return Some(QueuedJob {
-                        id: job.id,
-                        queue: job.queue,
-                        payload: job.payload,
-                        attempts: job.attempts,
-                        reserved_at: job.reserved_at.map(|dt| dt.into()),
-                        available_at: job.available_at.into(),
-                        created_at: job.created_at.into(),
+                        id: candidate.id,
+                        queue: candidate.queue,
+                        payload: candidate.payload,
+                        attempts,
+                        reserved_at: Some(now),
+                        available_at: candidate.available_at.into(),
+                        created_at: candidate.created_at.into(),
                     });
                 }
+                // Another worker got there first, try the next candidate
+                Ok(false) => continue,
+                Err(e) => {
+                    eprintln!("Failed to reserve job {}: {}", candidate.id, e);
+                    return None;
+                }
             }
         }
         None
@@ -95,22 +217,11 @@ impl QueueDriver for DatabaseDriver {
     }
 
     async fn release(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
-        let job = Entity::find_by_id(id)
-            .one(&self.db)
-            .await?;
+        self.unreserve(id, delay, 1).await
+    }
 
-        if let Some(job) = job {
-            let mut job: ActiveModel = job.into();
-            job.reserved_at = Set(None);
-            job.available_at = Set(if let Some(delay) = delay {
-                (Utc::now() + chrono::Duration::from_std(delay).unwrap()).into()
-            } else {
-                Utc::now().into()
-            });
-            job.attempts = Set(job.attempts.unwrap() + 1);
-            job.update(&self.db).await?;
-        }
-        Ok(())
+    async fn release_without_attempt(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.unreserve(id, delay, 0).await
     }
 
     async fn size(&self, queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
@@ -128,4 +239,205 @@ impl QueueDriver for DatabaseDriver {
             .await?;
         Ok(())
     }
-} 
\ No newline at end of file
+
+    async fn fail(&self, job: &QueuedJob, exception: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let failed = failed_job::ActiveModel {
+            id: Set(job.id),
+            queue: Set(job.queue.clone()),
+            payload: Set(job.payload.clone()),
+            exception: Set(exception.to_string()),
+            failed_at: Set(Utc::now().into()),
+        };
+
+        let txn = self.db.begin().await?;
+        failed_job::Entity::insert(failed).exec(&txn).await?;
+        Entity::delete_by_id(job.id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn failed_jobs(&self) -> Result<Vec<FailedJob>, Box<dyn std::error::Error>> {
+        let jobs = failed_job::Entity::find()
+            .order_by_desc(failed_job::Column::FailedAt)
+            .all(&self.db)
+            .await?;
+        Ok(jobs.into_iter().map(FailedJob::from).collect())
+    }
+
+    async fn find_failed(&self, id: Uuid) -> Result<Option<FailedJob>, Box<dyn std::error::Error>> {
+        let job = failed_job::Entity::find_by_id(id)
+            .one(&self.db)
+            .await?;
+        Ok(job.map(FailedJob::from))
+    }
+
+    async fn forget_failed(&self, id: Uuid) -> Result<bool, Box<dyn std::error::Error>> {
+        let result = failed_job::Entity::delete_by_id(id)
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn flush_failed(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let result = failed_job::Entity::delete_many()
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn retry_failed(&self, id: Uuid) -> Result<Option<Uuid>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin().await?;
+        let Some(failed) = failed_job::Entity::find_by_id(id).one(&txn).await? else {
+            return Ok(None);
+        };
+
+        let new_id = new_job(&failed.queue, failed.payload, Utc::now()).insert(&txn).await?.id;
+        failed_job::Entity::delete_by_id(id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(new_id))
+    }
+
+    async fn stats(&self, queue: &str) -> Result<QueueStats, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let jobs = || Entity::find().filter(Column::Queue.eq(queue));
+        let ready = || jobs().filter(Column::ReservedAt.is_null()).filter(Column::AvailableAt.lte(now));
+
+        let oldest = ready().order_by_asc(Column::CreatedAt).one(&self.db).await?;
+        Ok(QueueStats {
+            queue: queue.to_string(),
+            pending: ready().count(&self.db).await?,
+            reserved: jobs().filter(Column::ReservedAt.is_not_null()).count(&self.db).await?,
+            delayed: jobs()
+                .filter(Column::ReservedAt.is_null())
+                .filter(Column::AvailableAt.gt(now))
+                .count(&self.db)
+                .await?,
+            failed: failed_job::Entity::find()
+                .filter(failed_job::Column::Queue.eq(queue))
+                .count(&self.db)
+                .await?,
+            oldest_pending_seconds: oldest.map(|job| {
+                let created_at: DateTime<Utc> = job.created_at.into();
+                (now - created_at).num_seconds().max(0) as u64
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::testing::{create_table, sqlite_database};
+
+    async fn driver() -> DatabaseDriver {
+        let db = sqlite_database("sqlite::memory:").await;
+        create_table(&db, Entity).await;
+        create_table(&db, failed_job::Entity).await;
+        DatabaseDriver::new(db)
+    }
+
+    /// Drivers for several workers, each with its own connection to one database file
+    async fn workers(path: &std::path::Path, count: usize, retry_after: Duration) -> Vec<DatabaseDriver> {
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        let db = sqlite_database(&url).await;
+        create_table(&db, Entity).await;
+
+        let mut workers = Vec::new();
+        for _ in 0..count {
+            workers.push(DatabaseDriver::new(sqlite_database(&url).await).retry_after(retry_after));
+        }
+        workers
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_workers_reserve_each_job_once() {
+        let path = std::env::temp_dir().join(format!("ruskit-queue-{}.sqlite", Uuid::new_v4()));
+        let workers = workers(&path, 4, Duration::from_secs(90)).await;
+        for n in 0..20 {
+            workers[0].push("default", format!("job-{}", n), None).await.unwrap();
+        }
+
+        let popped = futures_util::future::join_all(workers.iter().map(|worker| async move {
+            let mut ids = Vec::new();
+            while let Some(job) = worker.pop("default").await {
+                ids.push(job.id);
+            }
+            ids
+        }))
+        .await;
+
+        let mut ids: Vec<Uuid> = popped.into_iter().flatten().collect();
+        assert_eq!(ids.len(), 20);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 20, "a job was reserved by more than one worker");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_reservations_are_picked_up_again() {
+        let path = std::env::temp_dir().join(format!("ruskit-queue-{}.sqlite", Uuid::new_v4()));
+        let workers = workers(&path, 2, Duration::from_millis(200)).await;
+        let id = workers[0].push("default", "import".to_string(), None).await.unwrap();
+
+        let (first, second) = tokio::join!(workers[0].pop("default"), workers[1].pop("default"));
+        assert_eq!(first.is_some() as u8 + second.is_some() as u8, 1, "exactly one worker wins the job");
+        assert_eq!(first.or(second).unwrap().attempts, 0);
+        assert!(workers[1].pop("default").await.is_none());
+
+        // The worker holding the job stops without deleting or releasing it
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let job = workers[1].pop("default").await.unwrap();
+        assert_eq!((job.id, job.attempts), (id, 1));
+        assert!(workers[0].pop("default").await.is_none());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_only_released_attempts_are_counted() {
+        let driver = driver().await;
+        driver.push("default", "send-invoice".to_string(), None).await.unwrap();
+
+        let job = driver.pop("default").await.unwrap();
+        driver.release(job.id, None).await.unwrap();
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.attempts, 1);
+
+        driver.release_without_attempt(job.id, None).await.unwrap();
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_jobs_can_be_retried_forgotten_and_flushed() {
+        let driver = driver().await;
+        for payload in ["send-invoice", "resize-avatar", "sync-crm"] {
+            driver.push("default", payload.to_string(), None).await.unwrap();
+            let job = driver.pop("default").await.unwrap();
+            driver.fail(&job, &format!("{} failed", payload)).await.unwrap();
+        }
+        assert_eq!(driver.size("default").await.unwrap(), 0);
+
+        let failed = driver.failed_jobs().await.unwrap();
+        assert_eq!(failed.len(), 3);
+        let invoice = failed.iter().find(|job| job.payload == "send-invoice").unwrap();
+        assert_eq!(invoice.exception, "send-invoice failed");
+        assert_eq!(driver.find_failed(invoice.id).await.unwrap().unwrap().queue, "default");
+
+        let retried = driver.retry_failed(invoice.id).await.unwrap().unwrap();
+        assert!(driver.find_failed(invoice.id).await.unwrap().is_none());
+        assert!(driver.retry_failed(invoice.id).await.unwrap().is_none());
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!((job.id, job.payload.as_str(), job.attempts), (retried, "send-invoice", 0));
+
+        let avatar = failed.iter().find(|job| job.payload == "resize-avatar").unwrap();
+        assert!(driver.forget_failed(avatar.id).await.unwrap());
+        assert!(!driver.forget_failed(avatar.id).await.unwrap());
+
+        assert_eq!(driver.flush_failed().await.unwrap(), 1);
+        assert!(driver.failed_jobs().await.unwrap().is_empty());
+    }
+}
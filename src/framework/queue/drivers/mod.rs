@@ -1,5 +1,11 @@
 mod database;
+mod null;
+mod redis;
 mod sqs;
+mod sync;
 
 pub use database::DatabaseDriver;
-pub use sqs::SqsDriver; 
\ No newline at end of file
+pub use null::NullDriver;
+pub use self::redis::RedisDriver;
+pub use sqs::SqsDriver;
+pub use sync::SyncDriver;
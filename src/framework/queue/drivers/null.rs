@@ -0,0 +1,41 @@
+use crate::framework::queue::{QueueDriver, QueuedJob};
+use async_trait::async_trait;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A queue driver that discards every job pushed onto it
+#[derive(Debug, Default)]
+pub struct NullDriver;
+
+impl NullDriver {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl QueueDriver for NullDriver {
+    async fn push(&self, _queue: &str, _payload: String, _delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        Ok(Uuid::new_v4())
+    }
+
+    async fn pop(&self, _queue: &str) -> Option<QueuedJob> {
+        None
+    }
+
+    async fn delete(&self, _id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn release(&self, _id: Uuid, _delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn size(&self, _queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(0)
+    }
+
+    async fn clear(&self, _queue: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
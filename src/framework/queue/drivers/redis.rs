@@ -0,0 +1,398 @@
+use crate::framework::queue::{FailedJob, QueueDriver, QueueStats, QueuedJob};
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use redis::aio::Connection;
+use redis::{AsyncCommands, Client, Script};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Moves due delayed jobs and expired reservations onto the ready list, then reserves the
+/// next ready job and returns its updated record. Running it as a script keeps the whole
+/// hand-off atomic across workers.
+///
+/// KEYS: ready list, delayed set, reserved set, jobs hash
+/// ARGV: current time in ms, reservation expiry in ms, current time as RFC 3339
+const POP_SCRIPT: &str = r#"
+local function migrate(from, now)
+    local ids = redis.call('zrangebyscore', from, '-inf', now)
+    for _, id in ipairs(ids) do
+        redis.call('zrem', from, id)
+        redis.call('rpush', KEYS[1], id)
+    end
+end
+
+migrate(KEYS[2], ARGV[1])
+migrate(KEYS[3], ARGV[1])
+
+local id = redis.call('lpop', KEYS[1])
+if not id then
+    return false
+end
+
+local record = redis.call('hget', KEYS[4], id)
+if not record then
+    -- The record was deleted while the id was still queued
+    return false
+end
+redis.call('zadd', KEYS[3], ARGV[2], id)
+
+-- A job that is still marked as reserved lost its worker, and that run counts as an attempt
+local job = cjson.decode(record)
+if job.reserved_at ~= cjson.null then
+    job.attempts = job.attempts + 1
+end
+job.reserved_at = ARGV[3]
+record = cjson.encode(job)
+redis.call('hset', KEYS[4], id, record)
+return record
+"#;
+
+/// A queue driver backed by Redis.
+///
+/// Ready jobs are kept in a list per queue, delayed jobs in a sorted set scored by the time
+/// they become available, and reserved jobs in a sorted set scored by the time their
+/// reservation expires. Job records themselves live in a single hash keyed by job id.
+pub struct RedisDriver {
+    client: Client,
+    prefix: String,
+    retry_after: Duration,
+}
+
+impl RedisDriver {
+    pub fn new(url: &str) -> Result<Self, redis::RedisError> {
+        let client = Client::open(url)?;
+        Ok(Self {
+            client,
+            prefix: "queues".to_string(),
+            retry_after: Duration::from_secs(90),
+        })
+    }
+
+    /// Set the prefix used for every key the driver writes
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Set how long a job may stay reserved before another worker may pick it up again
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    async fn connection(&self) -> Result<Connection, redis::RedisError> {
+        self.client.get_async_connection().await
+    }
+
+    fn ready_key(&self, queue: &str) -> String {
+        format!("{}:{}", self.prefix, queue)
+    }
+
+    fn delayed_key(&self, queue: &str) -> String {
+        format!("{}:{}:delayed", self.prefix, queue)
+    }
+
+    fn reserved_key(&self, queue: &str) -> String {
+        format!("{}:{}:reserved", self.prefix, queue)
+    }
+
+    fn jobs_key(&self) -> String {
+        format!("{}:jobs", self.prefix)
+    }
+
+    fn failed_key(&self) -> String {
+        format!("{}:failed", self.prefix)
+    }
+
+    async fn find(&self, conn: &mut Connection, id: Uuid) -> Result<Option<QueuedJob>, Box<dyn std::error::Error>> {
+        let record: Option<String> = conn.hget(self.jobs_key(), id.to_string()).await?;
+        match record {
+            Some(record) => Ok(Some(serde_json::from_str(&record)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Make a reserved job available again after the delay, adding to its attempts
+    async fn unreserve(&self, id: Uuid, delay: Option<Duration>, attempts: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let mut job = match self.find(&mut conn, id).await? {
+            Some(job) => job,
+            None => return Ok(()),
+        };
+
+        let now = Utc::now();
+        job.attempts += attempts;
+        job.reserved_at = None;
+        job.available_at = match delay {
+            Some(delay) => now + chrono::Duration::from_std(delay)?,
+            None => now,
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(self.jobs_key(), id.to_string(), serde_json::to_string(&job)?).ignore()
+            .zrem(self.reserved_key(&job.queue), id.to_string()).ignore();
+        if job.available_at > now {
+            pipe.zadd(self.delayed_key(&job.queue), id.to_string(), job.available_at.timestamp_millis()).ignore();
+        } else {
+            pipe.rpush(self.ready_key(&job.queue), id.to_string()).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn reserve_next(&self, queue: &str) -> Result<Option<QueuedJob>, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::from_std(self.retry_after)?;
+
+        let record: Option<String> = Script::new(POP_SCRIPT)
+            .key(self.ready_key(queue))
+            .key(self.delayed_key(queue))
+            .key(self.reserved_key(queue))
+            .key(self.jobs_key())
+            .arg(now.timestamp_millis())
+            .arg(expires_at.timestamp_millis())
+            .arg(now.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            .invoke_async(&mut conn)
+            .await?;
+
+        match record {
+            Some(record) => Ok(Some(serde_json::from_str(&record)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl QueueDriver for RedisDriver {
+    async fn push(&self, queue: &str, payload: String, delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let available_at = match delay {
+            Some(delay) => now + chrono::Duration::from_std(delay)?,
+            None => now,
+        };
+
+        let job = QueuedJob {
+            id,
+            queue: queue.to_string(),
+            payload,
+            attempts: 0,
+            reserved_at: None,
+            available_at,
+            created_at: now,
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(self.jobs_key(), id.to_string(), serde_json::to_string(&job)?)
+            .ignore();
+        if available_at > now {
+            pipe.zadd(self.delayed_key(queue), id.to_string(), available_at.timestamp_millis()).ignore();
+        } else {
+            pipe.rpush(self.ready_key(queue), id.to_string()).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+
+        Ok(id)
+    }
+
+    async fn pop(&self, queue: &str) -> Option<QueuedJob> {
+        match self.reserve_next(queue).await {
+            Ok(job) => job,
+            Err(e) => {
+                eprintln!("Failed to fetch next job from queue {}: {}", queue, e);
+                None
+            }
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let job = match self.find(&mut conn, id).await? {
+            Some(job) => job,
+            None => return Ok(()),
+        };
+
+        redis::pipe()
+            .atomic()
+            .zrem(self.reserved_key(&job.queue), id.to_string()).ignore()
+            .zrem(self.delayed_key(&job.queue), id.to_string()).ignore()
+            .lrem(self.ready_key(&job.queue), 0, id.to_string()).ignore()
+            .hdel(self.jobs_key(), id.to_string()).ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.unreserve(id, delay, 1).await
+    }
+
+    async fn release_without_attempt(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.unreserve(id, delay, 0).await
+    }
+
+    async fn size(&self, queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let (ready, delayed, reserved): (u64, u64, u64) = redis::pipe()
+            .llen(self.ready_key(queue))
+            .zcard(self.delayed_key(queue))
+            .zcard(self.reserved_key(queue))
+            .query_async(&mut conn)
+            .await?;
+        Ok(ready + delayed + reserved)
+    }
+
+    async fn clear(&self, queue: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let (mut ids, delayed, reserved): (Vec<String>, Vec<String>, Vec<String>) = redis::pipe()
+            .lrange(self.ready_key(queue), 0, -1)
+            .zrange(self.delayed_key(queue), 0, -1)
+            .zrange(self.reserved_key(queue), 0, -1)
+            .query_async(&mut conn)
+            .await?;
+        ids.extend(delayed);
+        ids.extend(reserved);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(self.ready_key(queue)).ignore()
+            .del(self.delayed_key(queue)).ignore()
+            .del(self.reserved_key(queue)).ignore();
+        if !ids.is_empty() {
+            pipe.hdel(self.jobs_key(), ids).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn fail(&self, job: &QueuedJob, exception: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let failed = FailedJob {
+            id: job.id,
+            queue: job.queue.clone(),
+            payload: job.payload.clone(),
+            exception: exception.to_string(),
+            failed_at: Utc::now(),
+        };
+
+        let mut conn = self.connection().await?;
+        redis::pipe()
+            .atomic()
+            .hset(self.failed_key(), job.id.to_string(), serde_json::to_string(&failed)?).ignore()
+            .zrem(self.reserved_key(&job.queue), job.id.to_string()).ignore()
+            .zrem(self.delayed_key(&job.queue), job.id.to_string()).ignore()
+            .lrem(self.ready_key(&job.queue), 0, job.id.to_string()).ignore()
+            .hdel(self.jobs_key(), job.id.to_string()).ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn failed_jobs(&self) -> Result<Vec<FailedJob>, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let records: Vec<String> = conn.hvals(self.failed_key()).await?;
+        let mut jobs = records
+            .iter()
+            .map(|record| serde_json::from_str::<FailedJob>(record))
+            .collect::<Result<Vec<_>, _>>()?;
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.failed_at));
+        Ok(jobs)
+    }
+
+    async fn find_failed(&self, id: Uuid) -> Result<Option<FailedJob>, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let record: Option<String> = conn.hget(self.failed_key(), id.to_string()).await?;
+        match record {
+            Some(record) => Ok(Some(serde_json::from_str(&record)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn forget_failed(&self, id: Uuid) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let removed: u64 = conn.hdel(self.failed_key(), id.to_string()).await?;
+        Ok(removed > 0)
+    }
+
+    async fn flush_failed(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let (count, _): (u64, u64) = redis::pipe()
+            .atomic()
+            .hlen(self.failed_key())
+            .del(self.failed_key())
+            .query_async(&mut conn)
+            .await?;
+        Ok(count)
+    }
+
+    async fn stats(&self, queue: &str) -> Result<QueueStats, Box<dyn std::error::Error>> {
+        let mut conn = self.connection().await?;
+        let now = Utc::now();
+        let (ready, due, delayed, reserved, oldest_id): (u64, u64, u64, u64, Option<String>) = redis::pipe()
+            .llen(self.ready_key(queue))
+            .zcount(self.delayed_key(queue), "-inf", now.timestamp_millis())
+            .zcount(self.delayed_key(queue), format!("({}", now.timestamp_millis()), "+inf")
+            .zcard(self.reserved_key(queue))
+            .lindex(self.ready_key(queue), 0)
+            .query_async(&mut conn)
+            .await?;
+
+        let oldest = match oldest_id.and_then(|id| Uuid::parse_str(&id).ok()) {
+            Some(id) => self.find(&mut conn, id).await?,
+            None => None,
+        };
+        let failed = self
+            .failed_jobs()
+            .await?
+            .iter()
+            .filter(|job| job.queue == queue)
+            .count() as u64;
+
+        Ok(QueueStats {
+            queue: queue.to_string(),
+            // Delayed jobs that are due only move to the ready list when a worker pops
+            pending: ready + due,
+            reserved,
+            delayed,
+            failed,
+            oldest_pending_seconds: oldest.map(|job| (now - job.created_at).num_seconds().max(0) as u64),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run with `REDIS_URL=redis://127.0.0.1:6379 cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore = "needs a Redis server in REDIS_URL"]
+    async fn test_redis_queue_roundtrip() {
+        let url = std::env::var("REDIS_URL").expect("REDIS_URL must point to a Redis server");
+        let prefix = format!("test-queues-{}", Uuid::new_v4());
+        let driver = RedisDriver::new(&url).unwrap().prefix(&prefix);
+
+        let first = driver.push("default", "first".to_string(), None).await.unwrap();
+        driver.push("default", "later".to_string(), Some(Duration::from_secs(60))).await.unwrap();
+        assert_eq!(driver.size("default").await.unwrap(), 2);
+
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.id, first);
+        assert!(driver.pop("default").await.is_none());
+
+        driver.release(job.id, None).await.unwrap();
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.attempts, 1);
+
+        driver.fail(&job, "boom").await.unwrap();
+        assert_eq!(driver.failed_jobs().await.unwrap().len(), 1);
+        assert!(driver.forget_failed(job.id).await.unwrap());
+
+        driver.clear("default").await.unwrap();
+        assert_eq!(driver.size("default").await.unwrap(), 0);
+    }
+}
@@ -1,45 +1,452 @@
-use crate::framework::queue::{QueueDriver, QueuedJob};
+use crate::framework::aws::{Credentials, SignatureV4};
+use crate::framework::queue::{FailedJob, QueueDriver, QueueStats, QueuedJob};
 use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
 use uuid::Uuid;
 
+/// The longest delay SQS accepts for `DelaySeconds`
+const MAX_DELAY_SECONDS: u64 = 900;
+
+/// The longest visibility timeout SQS accepts
+const MAX_VISIBILITY_SECONDS: u64 = 43_200;
+
+/// The message body written to SQS, carrying the id the rest of the queue system knows the job by
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    id: Uuid,
+    payload: String,
+}
+
+/// The receipt for a message this driver has received and not yet deleted or released
+struct Receipt {
+    handle: String,
+    queue_url: String,
+}
+
+/// A queue driver for Amazon SQS and SQS-compatible services such as ElasticMQ.
+///
+/// Requests use the SQS JSON protocol and are signed with Signature Version 4. The queue URL
+/// is used for the `default` queue; any other queue name resolves to a sibling queue on the
+/// same account, e.g. `https://sqs.us-east-1.amazonaws.com/123456789012/emails`.
+/// Pushing a job with a delay longer than SQS allows, 15 minutes, returns an error.
 pub struct SqsDriver {
+    client: reqwest::Client,
     region: String,
     queue_url: String,
+    endpoint: String,
+    credentials: Option<Credentials>,
+    retry_after: Duration,
+    failed_queue_url: Option<String>,
+    receipts: Mutex<HashMap<Uuid, Receipt>>,
 }
 
 impl SqsDriver {
     pub fn new(region: &str, queue_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let url = Url::parse(queue_url)?;
+        let endpoint = url.origin().ascii_serialization();
         Ok(Self {
+            client: reqwest::Client::new(),
             region: region.to_string(),
-            queue_url: queue_url.to_string(),
+            queue_url: queue_url.trim_end_matches('/').to_string(),
+            endpoint,
+            credentials: Credentials::from_env(),
+            retry_after: Duration::from_secs(90),
+            failed_queue_url: None,
+            receipts: Mutex::new(HashMap::new()),
         })
     }
+
+    /// Send requests to a different endpoint than the queue URL's host, e.g. a local ElasticMQ
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sign requests with these credentials instead of the ones in the environment
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Set how long a received message stays invisible to other workers
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Send failed jobs to this queue. Without one, failed jobs are deleted.
+    pub fn failed_queue_url(mut self, url: &str) -> Self {
+        self.failed_queue_url = Some(url.to_string());
+        self
+    }
+
+    /// Resolve a queue name to its URL
+    fn queue_url_for(&self, queue: &str) -> String {
+        match self.queue_url.rsplit_once('/') {
+            Some((_, name)) if queue == "default" || queue == name => self.queue_url.clone(),
+            Some((base, _)) => format!("{}/{}", base, queue),
+            None => self.queue_url.clone(),
+        }
+    }
+
+    /// Call an SQS action and return its JSON response
+    async fn call(&self, action: &str, body: Value) -> Result<Value, Box<dyn std::error::Error>> {
+        let credentials = self
+            .credentials
+            .clone()
+            .ok_or("AWS credentials not configured for the SQS driver")?;
+        let url = Url::parse(&format!("{}/", self.endpoint))?;
+        let body = serde_json::to_vec(&body)?;
+
+        let mut headers = vec![
+            ("content-type".to_string(), "application/x-amz-json-1.0".to_string()),
+            ("x-amz-target".to_string(), format!("AmazonSQS.{}", action)),
+        ];
+        SignatureV4::new(credentials, &self.region, "sqs").sign("POST", &url, &mut headers, &body, Utc::now());
+
+        let mut request = self.client.post(url).body(body);
+        for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let error: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+            let message = error
+                .get("message")
+                .or_else(|| error.get("Message"))
+                .and_then(Value::as_str)
+                .unwrap_or(&text);
+            return Err(format!("SQS {} failed ({}): {}", action, status, message).into());
+        }
+
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn send(&self, queue_url: &str, body: String, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        let delay = delay.map(|d| d.as_secs()).unwrap_or(0);
+        if delay > MAX_DELAY_SECONDS {
+            return Err(format!(
+                "SQS cannot delay a message by {} seconds, the maximum is {}",
+                delay, MAX_DELAY_SECONDS
+            )
+            .into());
+        }
+        self.call(
+            "SendMessage",
+            json!({ "QueueUrl": queue_url, "MessageBody": body, "DelaySeconds": delay }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn receive(&self, queue: &str) -> Result<Option<QueuedJob>, Box<dyn std::error::Error>> {
+        let queue_url = self.queue_url_for(queue);
+        let response = self
+            .call(
+                "ReceiveMessage",
+                json!({
+                    "QueueUrl": queue_url,
+                    "MaxNumberOfMessages": 1,
+                    "VisibilityTimeout": self.retry_after.as_secs().min(MAX_VISIBILITY_SECONDS),
+                    "AttributeNames": ["ApproximateReceiveCount", "SentTimestamp"],
+                }),
+            )
+            .await?;
+
+        let message = match response.get("Messages").and_then(|m| m.get(0)) {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let handle = message
+            .get("ReceiptHandle")
+            .and_then(Value::as_str)
+            .ok_or("SQS message has no receipt handle")?;
+        let body = message.get("Body").and_then(Value::as_str).unwrap_or_default();
+        let attribute = |name: &str| {
+            message
+                .get("Attributes")
+                .and_then(|a| a.get(name))
+                .and_then(Value::as_str)
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+
+        // Messages not written by this driver are still handed to the worker, which fails them as malformed
+        let envelope = serde_json::from_str::<Envelope>(body).unwrap_or_else(|_| Envelope {
+            id: message
+                .get("MessageId")
+                .and_then(Value::as_str)
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .unwrap_or_else(Uuid::new_v4),
+            payload: body.to_string(),
+        });
+
+        let now = Utc::now();
+        let created_at = attribute("SentTimestamp")
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+            .unwrap_or(now);
+
+        self.receipts.lock().unwrap().insert(
+            envelope.id,
+            Receipt {
+                handle: handle.to_string(),
+                queue_url,
+            },
+        );
+
+        Ok(Some(QueuedJob {
+            id: envelope.id,
+            queue: queue.to_string(),
+            payload: envelope.payload,
+            attempts: attribute("ApproximateReceiveCount").map(|n| (n - 1).max(0) as u32).unwrap_or(0),
+            reserved_at: Some(now),
+            available_at: now,
+            created_at,
+        }))
+    }
+
+    fn take_receipt(&self, id: Uuid) -> Option<Receipt> {
+        self.receipts.lock().unwrap().remove(&id)
+    }
 }
 
 #[async_trait]
 impl QueueDriver for SqsDriver {
-    async fn push(&self, _queue: &str, _payload: String, _delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn push(&self, queue: &str, payload: String, delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let id = Uuid::new_v4();
+        let body = serde_json::to_string(&Envelope { id, payload })?;
+        self.send(&self.queue_url_for(queue), body, delay).await?;
+        Ok(id)
     }
 
-    async fn pop(&self, _queue: &str) -> Option<QueuedJob> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn pop(&self, queue: &str) -> Option<QueuedJob> {
+        match self.receive(queue).await {
+            Ok(job) => job,
+            Err(e) => {
+                eprintln!("Failed to fetch next job from queue {}: {}", queue, e);
+                None
+            }
+        }
     }
 
-    async fn delete(&self, _id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn delete(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        let receipt = match self.take_receipt(id) {
+            Some(receipt) => receipt,
+            None => return Ok(()),
+        };
+        self.call(
+            "DeleteMessage",
+            json!({ "QueueUrl": receipt.queue_url, "ReceiptHandle": receipt.handle }),
+        )
+        .await?;
+        Ok(())
     }
 
-    async fn release(&self, _id: Uuid, _delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn release(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        let receipt = match self.take_receipt(id) {
+            Some(receipt) => receipt,
+            None => return Ok(()),
+        };
+        let delay = delay.map(|d| d.as_secs().min(MAX_VISIBILITY_SECONDS)).unwrap_or(0);
+        self.call(
+            "ChangeMessageVisibility",
+            json!({
+                "QueueUrl": receipt.queue_url,
+                "ReceiptHandle": receipt.handle,
+                "VisibilityTimeout": delay,
+            }),
+        )
+        .await?;
+        Ok(())
     }
 
-    async fn size(&self, _queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn size(&self, queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let stats = self.stats(queue).await?;
+        Ok(stats.pending + stats.reserved + stats.delayed)
     }
 
-    async fn clear(&self, _queue: &str) -> Result<(), Box<dyn std::error::Error>> {
-        unimplemented!("SQS driver not yet implemented")
+    async fn clear(&self, queue: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.call("PurgeQueue", json!({ "QueueUrl": self.queue_url_for(queue) })).await?;
+        Ok(())
+    }
+
+    async fn stats(&self, queue: &str) -> Result<QueueStats, Box<dyn std::error::Error>> {
+        let response = self
+            .call(
+                "GetQueueAttributes",
+                json!({
+                    "QueueUrl": self.queue_url_for(queue),
+                    "AttributeNames": [
+                        "ApproximateNumberOfMessages",
+                        "ApproximateNumberOfMessagesNotVisible",
+                        "ApproximateNumberOfMessagesDelayed",
+                    ],
+                }),
+            )
+            .await?;
+        let attribute = |name: &str| {
+            response
+                .get("Attributes")
+                .and_then(|a| a.get(name))
+                .and_then(Value::as_str)
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+
+        Ok(QueueStats {
+            queue: queue.to_string(),
+            pending: attribute("ApproximateNumberOfMessages"),
+            reserved: attribute("ApproximateNumberOfMessagesNotVisible"),
+            delayed: attribute("ApproximateNumberOfMessagesDelayed"),
+            ..Default::default()
+        })
     }
-} 
\ No newline at end of file
+
+    async fn fail(&self, job: &QueuedJob, exception: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // Leaving the message in place would make it visible again and retry it forever
+        let Some(failed_queue_url) = self.failed_queue_url.as_deref() else {
+            eprintln!(
+                "No failed queue configured for the SQS driver, deleting failed job {}: {}",
+                job.id, exception
+            );
+            return self.delete(job.id).await;
+        };
+
+        let failed = FailedJob {
+            id: job.id,
+            queue: job.queue.clone(),
+            payload: job.payload.clone(),
+            exception: exception.to_string(),
+            failed_at: Utc::now(),
+        };
+        self.send(failed_queue_url, serde_json::to_string(&failed)?, None).await?;
+        self.delete(job.id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::HeaderMap, routing::post, Json, Router};
+    use std::sync::Arc;
+
+    /// An in-memory stand-in for SQS that understands the actions the driver uses
+    async fn mock_sqs() -> String {
+        let messages: Arc<Mutex<Vec<(String, String, u32, bool)>>> = Arc::default();
+        let app = Router::new().route(
+            "/",
+            post(move |headers: HeaderMap, body: String| {
+                let messages = messages.clone();
+                async move {
+                    assert!(headers.contains_key("authorization"));
+                    let body: Value = serde_json::from_str(&body).unwrap();
+                    let target = headers["x-amz-target"].to_str().unwrap().to_string();
+                    let mut messages = messages.lock().unwrap();
+                    let response = match target.trim_start_matches("AmazonSQS.") {
+                        "SendMessage" => {
+                            let handle = Uuid::new_v4().to_string();
+                            let visible = body["DelaySeconds"].as_u64() == Some(0);
+                            messages.push((handle, body["MessageBody"].as_str().unwrap().to_string(), 0, visible));
+                            json!({ "MessageId": Uuid::new_v4() })
+                        }
+                        "ReceiveMessage" => match messages.iter_mut().find(|m| m.3) {
+                            Some(message) => {
+                                message.2 += 1;
+                                message.3 = false;
+                                json!({ "Messages": [{
+                                    "MessageId": Uuid::new_v4(),
+                                    "ReceiptHandle": message.0,
+                                    "Body": message.1,
+                                    "Attributes": { "ApproximateReceiveCount": message.2.to_string() },
+                                }] })
+                            }
+                            None => json!({}),
+                        },
+                        "DeleteMessage" => {
+                            messages.retain(|m| m.0 != body["ReceiptHandle"]);
+                            json!({})
+                        }
+                        "ChangeMessageVisibility" => {
+                            for message in messages.iter_mut().filter(|m| m.0 == body["ReceiptHandle"]) {
+                                message.3 = body["VisibilityTimeout"].as_u64() == Some(0);
+                            }
+                            json!({})
+                        }
+                        "GetQueueAttributes" => json!({ "Attributes": {
+                            "ApproximateNumberOfMessages": messages.len().to_string(),
+                        } }),
+                        other => panic!("unexpected action {}", other),
+                    };
+                    Json(response)
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_sqs_queue_roundtrip() {
+        let endpoint = mock_sqs().await;
+        let driver = SqsDriver::new("us-east-1", "https://sqs.us-east-1.amazonaws.com/123456789012/default")
+            .unwrap()
+            .endpoint(&endpoint)
+            .credentials(Credentials::new("AKIDEXAMPLE", "secret"));
+
+        let id = driver.push("default", "first".to_string(), None).await.unwrap();
+        assert_eq!(driver.size("default").await.unwrap(), 1);
+
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.id, id);
+        assert_eq!(job.payload, "first");
+        assert_eq!(job.attempts, 0);
+        assert!(driver.pop("default").await.is_none());
+
+        driver.release(job.id, None).await.unwrap();
+        let job = driver.pop("default").await.unwrap();
+        assert_eq!(job.attempts, 1);
+
+        driver.delete(job.id).await.unwrap();
+        assert_eq!(driver.size("default").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_jobs_are_deleted_without_a_failed_queue() {
+        let endpoint = mock_sqs().await;
+        let driver = SqsDriver::new("us-east-1", "https://sqs.us-east-1.amazonaws.com/123456789012/default")
+            .unwrap()
+            .endpoint(&endpoint)
+            .credentials(Credentials::new("AKIDEXAMPLE", "secret"));
+
+        let too_long = driver.push("default", "report".to_string(), Some(Duration::from_secs(901))).await;
+        assert!(too_long.is_err());
+        assert_eq!(driver.size("default").await.unwrap(), 0);
+
+        driver.push("default", "report".to_string(), None).await.unwrap();
+        let job = driver.pop("default").await.unwrap();
+        driver.fail(&job, "boom").await.unwrap();
+        assert_eq!(driver.size("default").await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_queue_names_resolve_to_sibling_urls() {
+        let driver = SqsDriver::new("us-east-1", "https://sqs.us-east-1.amazonaws.com/123456789012/jobs").unwrap();
+        assert_eq!(driver.queue_url_for("default"), "https://sqs.us-east-1.amazonaws.com/123456789012/jobs");
+        assert_eq!(driver.queue_url_for("jobs"), "https://sqs.us-east-1.amazonaws.com/123456789012/jobs");
+        assert_eq!(driver.queue_url_for("emails"), "https://sqs.us-east-1.amazonaws.com/123456789012/emails");
+    }
+}
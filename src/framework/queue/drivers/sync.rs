@@ -0,0 +1,96 @@
+use crate::framework::queue::worker::run_inline;
+use crate::framework::queue::{QueueDriver, QueuedJob};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A queue driver that runs jobs as soon as they are dispatched, without a worker.
+/// Delays are ignored, a job gets a single attempt and its error is returned from `dispatch`.
+#[derive(Debug, Default)]
+pub struct SyncDriver;
+
+impl SyncDriver {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl QueueDriver for SyncDriver {
+    async fn push(&self, queue: &str, payload: String, _delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let job = QueuedJob {
+            id: Uuid::new_v4(),
+            queue: queue.to_string(),
+            payload,
+            attempts: 0,
+            reserved_at: Some(now),
+            available_at: now,
+            created_at: now,
+        };
+        run_inline(&job).await?;
+        Ok(job.id)
+    }
+
+    async fn pop(&self, _queue: &str) -> Option<QueuedJob> {
+        None
+    }
+
+    async fn delete(&self, _id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn release(&self, _id: Uuid, _delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn size(&self, _queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(0)
+    }
+
+    async fn clear(&self, _queue: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::queue::job::JobWrapper;
+    use crate::framework::queue::{job, Job};
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static SENT: AtomicU32 = AtomicU32::new(0);
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendReceipt {
+        valid: bool,
+    }
+
+    #[job]
+    #[async_trait]
+    impl Job for SendReceipt {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            if !self.valid {
+                return Err("invalid receipt".into());
+            }
+            SENT.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_driver_runs_jobs_inline() {
+        let driver = SyncDriver::new();
+        let payload = |valid| serde_json::to_string(&JobWrapper::new(&SendReceipt { valid }).unwrap()).unwrap();
+
+        driver.push("default", payload(true), Some(Duration::from_secs(60))).await.unwrap();
+        assert_eq!(SENT.load(Ordering::SeqCst), 1);
+
+        let error = driver.push("default", payload(false), None).await.unwrap_err();
+        assert_eq!(error.to_string(), "invalid receipt");
+        assert_eq!(SENT.load(Ordering::SeqCst), 1);
+    }
+}
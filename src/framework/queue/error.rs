@@ -0,0 +1,7 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("An identical {0} job is already queued")]
+    AlreadyQueued(String),
+}
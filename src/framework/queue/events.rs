@@ -0,0 +1,74 @@
+use crate::framework::queue::{Queue, QueuedJob};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+type Hook = Arc<dyn Fn(&JobEvent) + Send + Sync>;
+
+static HOOKS: Lazy<RwLock<Hooks>> = Lazy::new(|| RwLock::new(Hooks::default()));
+
+#[derive(Default)]
+struct Hooks {
+    before: Vec<Hook>,
+    after: Vec<Hook>,
+    exception_occurred: Vec<Hook>,
+    failing: Vec<Hook>,
+}
+
+/// The job a lifecycle hook is called for
+#[derive(Debug, Clone, Copy)]
+pub struct JobEvent<'a> {
+    pub job: &'a QueuedJob,
+    /// The type of the job, unless its payload couldn't be read
+    pub job_type: Option<&'a str>,
+    /// The error the job failed with
+    pub exception: Option<&'a str>,
+}
+
+fn call(hooks: impl Fn(&Hooks) -> &Vec<Hook>, event: JobEvent) {
+    // Clone the hooks so one of them may register another without deadlocking
+    let hooks = hooks(&HOOKS.read().unwrap()).clone();
+    for hook in hooks {
+        hook(&event);
+    }
+}
+
+pub(crate) fn job_processing(job: &QueuedJob, job_type: &str) {
+    call(|hooks| &hooks.before, JobEvent { job, job_type: Some(job_type), exception: None });
+}
+
+pub(crate) fn job_processed(job: &QueuedJob, job_type: &str) {
+    call(|hooks| &hooks.after, JobEvent { job, job_type: Some(job_type), exception: None });
+}
+
+pub(crate) fn job_exception_occurred(job: &QueuedJob, job_type: &str, exception: &str) {
+    call(
+        |hooks| &hooks.exception_occurred,
+        JobEvent { job, job_type: Some(job_type), exception: Some(exception) },
+    );
+}
+
+pub(crate) fn job_failed(job: &QueuedJob, job_type: Option<&str>, exception: &str) {
+    call(|hooks| &hooks.failing, JobEvent { job, job_type, exception: Some(exception) });
+}
+
+impl Queue {
+    /// Register a hook called before a worker runs a job
+    pub fn before(hook: impl Fn(&JobEvent) + Send + Sync + 'static) {
+        HOOKS.write().unwrap().before.push(Arc::new(hook));
+    }
+
+    /// Register a hook called after a job ran successfully
+    pub fn after(hook: impl Fn(&JobEvent) + Send + Sync + 'static) {
+        HOOKS.write().unwrap().after.push(Arc::new(hook));
+    }
+
+    /// Register a hook called whenever an attempt of a job fails, including the last one
+    pub fn exception_occurred(hook: impl Fn(&JobEvent) + Send + Sync + 'static) {
+        HOOKS.write().unwrap().exception_occurred.push(Arc::new(hook));
+    }
+
+    /// Register a hook called when a job has failed for good and is moved to the failed jobs
+    pub fn failing(hook: impl Fn(&JobEvent) + Send + Sync + 'static) {
+        HOOKS.write().unwrap().failing.push(Arc::new(hook));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::framework::queue::job::JobWrapper;
+use crate::framework::queue::{Job, Queue, QueueDriver, QueuedJob};
+use crate::framework::testing::fake::{FakeGuard, FakeSlot};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+type SharedDriver = Arc<RwLock<Box<dyn QueueDriver + Send + Sync>>>;
+type Recorded = Arc<Mutex<Vec<PushedJob>>>;
+
+static FAKE: FakeSlot<(SharedDriver, Recorded)> = FakeSlot::new();
+
+/// A job recorded by the fake queue
+#[derive(Debug, Clone)]
+pub struct PushedJob {
+    pub id: Uuid,
+    pub queue: String,
+    pub payload: String,
+    pub delay: Option<Duration>,
+}
+
+impl PushedJob {
+    /// The type name of the job, if the payload was dispatched as a job
+    pub fn job_type(&self) -> Option<String> {
+        serde_json::from_str::<JobWrapper>(&self.payload).ok().map(|wrapper| wrapper.job_type)
+    }
+
+    /// Deserialize the job if it is of type `T`
+    pub fn job<T: Job + DeserializeOwned>(&self) -> Option<T> {
+        let wrapper = serde_json::from_str::<JobWrapper>(&self.payload).ok()?;
+        if wrapper.job_type != T::type_name() {
+            return None;
+        }
+        serde_json::from_str(&wrapper.data().ok()?).ok()
+    }
+}
+
+/// A queue driver that records pushed jobs and keeps them in memory, so tests can check what
+/// was dispatched or run the jobs with a worker
+pub struct FakeDriver {
+    pushed: Recorded,
+    queued: Mutex<Vec<QueuedJob>>,
+}
+
+impl FakeDriver {
+    /// Make a reserved job available again after the delay, adding to its attempts
+    fn unreserve(&self, id: Uuid, delay: Option<Duration>, attempts: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let available_at = Utc::now() + chrono::Duration::from_std(delay.unwrap_or_default())?;
+        if let Some(job) = self.queued.lock().unwrap().iter_mut().find(|job| job.id == id) {
+            job.attempts += attempts;
+            job.reserved_at = None;
+            job.available_at = available_at;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl QueueDriver for FakeDriver {
+    async fn push(&self, queue: &str, payload: String, delay: Option<Duration>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        self.queued.lock().unwrap().push(QueuedJob {
+            id,
+            queue: queue.to_string(),
+            payload: payload.clone(),
+            attempts: 0,
+            reserved_at: None,
+            available_at: now + chrono::Duration::from_std(delay.unwrap_or_default())?,
+            created_at: now,
+        });
+        self.pushed.lock().unwrap().push(PushedJob {
+            id,
+            queue: queue.to_string(),
+            payload,
+            delay,
+        });
+        Ok(id)
+    }
+
+    async fn pop(&self, queue: &str) -> Option<QueuedJob> {
+        let now = Utc::now();
+        let mut queued = self.queued.lock().unwrap();
+        let job = queued
+            .iter_mut()
+            .find(|job| job.queue == queue && job.reserved_at.is_none() && job.available_at <= now)?;
+        job.reserved_at = Some(now);
+        Some(job.clone())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        self.queued.lock().unwrap().retain(|job| job.id != id);
+        self.pushed.lock().unwrap().retain(|job| job.id != id);
+        Ok(())
+    }
+
+    async fn release(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.unreserve(id, delay, 1)
+    }
+
+    async fn release_without_attempt(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.unreserve(id, delay, 0)
+    }
+
+    async fn size(&self, queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(self.pushed.lock().unwrap().iter().filter(|job| job.queue == queue).count() as u64)
+    }
+
+    async fn clear(&self, queue: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.queued.lock().unwrap().retain(|job| job.queue != queue);
+        self.pushed.lock().unwrap().retain(|job| job.queue != queue);
+        Ok(())
+    }
+}
+
+/// The fake driver, if one is installed
+pub(crate) fn current() -> Option<SharedDriver> {
+    FAKE.current().map(|(driver, _)| driver)
+}
+
+fn pushed() -> Vec<PushedJob> {
+    match FAKE.current() {
+        Some((_, pushed)) => pushed.lock().unwrap().clone(),
+        None => panic!("Queue is not faked, call Queue::fake() first"),
+    }
+}
+
+impl Queue {
+    /// Replace the queue driver with a recorder until the returned guard is dropped. Other
+    /// tests that fake the queue wait for the guard, since the fake is shared by every thread.
+    pub fn fake() -> FakeGuard {
+        let pushed = Arc::new(Mutex::new(Vec::new()));
+        let driver: Box<dyn QueueDriver + Send + Sync> = Box::new(FakeDriver {
+            pushed: pushed.clone(),
+            queued: Mutex::new(Vec::new()),
+        });
+        FAKE.install((Arc::new(RwLock::new(driver)), pushed))
+    }
+
+    /// Get every job pushed to the fake queue
+    pub fn pushed() -> Vec<PushedJob> {
+        pushed()
+    }
+
+    /// Get the pushed jobs of type `T`
+    pub fn pushed_jobs<T: Job + DeserializeOwned>() -> Vec<T> {
+        pushed().iter().filter_map(|job| job.job::<T>()).collect()
+    }
+
+    /// Assert that a job of type `T` matching the predicate was pushed
+    pub fn assert_pushed<T: Job + DeserializeOwned>(predicate: impl Fn(&T) -> bool) {
+        assert!(
+            Self::pushed_jobs::<T>().iter().any(predicate),
+            "Expected a matching {} job to be pushed",
+            T::type_name()
+        );
+    }
+
+    /// Assert that a job of type `T` was pushed onto the given queue
+    pub fn assert_pushed_on<T: Job + DeserializeOwned>(queue: &str) {
+        assert!(
+            pushed().iter().any(|job| job.queue == queue && job.job::<T>().is_some()),
+            "Expected a {} job to be pushed onto queue {}",
+            T::type_name(),
+            queue
+        );
+    }
+
+    /// Assert that no job of type `T` was pushed
+    pub fn assert_not_pushed<T: Job + DeserializeOwned>() {
+        let count = Self::pushed_jobs::<T>().len();
+        assert!(count == 0, "Expected no {} job to be pushed, but {} were", T::type_name(), count);
+    }
+
+    /// Assert that no jobs were pushed at all
+    pub fn assert_nothing_pushed() {
+        let pushed = pushed();
+        assert!(
+            pushed.is_empty(),
+            "Expected no jobs to be pushed, but {} were: {:?}",
+            pushed.len(),
+            pushed.iter().map(|job| job.job_type().unwrap_or_default()).collect::<Vec<_>>()
+        );
+    }
+
+    /// Assert that a job of type `T` was pushed exactly `times` times
+    pub fn assert_pushed_times<T: Job + DeserializeOwned>(times: usize) {
+        let count = Self::pushed_jobs::<T>().len();
+        assert!(
+            count == times,
+            "Expected {} job to be pushed {} times, but it was pushed {} times",
+            T::type_name(),
+            times,
+            count
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::jobs::TestJob;
+
+    #[tokio::test]
+    async fn test_fake_records_dispatched_jobs() {
+        let fake = Queue::fake();
+        Queue::assert_nothing_pushed();
+
+        Queue::dispatch(TestJob { message: "hello".to_string() }).await.unwrap();
+        // Jobs dispatched from other threads are recorded too
+        std::thread::spawn(|| {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(Queue::dispatch(TestJob { message: "again".to_string() }))
+                .map_err(|e| e.to_string())
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+
+        Queue::assert_pushed::<TestJob>(|job| job.message == "hello");
+        Queue::assert_pushed_on::<TestJob>("default");
+        Queue::assert_pushed_times::<TestJob>(2);
+        assert_eq!(Queue::size("default").await.unwrap(), 2);
+
+        drop(fake);
+        let _fake = Queue::fake();
+        Queue::assert_not_pushed::<TestJob>();
+    }
+}
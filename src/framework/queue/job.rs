@@ -1,6 +1,55 @@
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use std::any::Any;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::framework::cache::Cache;
+use crate::framework::crypt::Crypt;
+use crate::framework::queue::Queue;
+use crate::framework::queue::error::QueueError;
+use crate::framework::queue::middleware::{unique_key, JobMiddleware};
+
+/// How long to wait before retrying a failed job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait the same number of seconds before every retry
+    Fixed(u64),
+    /// Wait the given number of seconds for each attempt, e.g. `[10, 60, 300]`.
+    /// The last value is reused once the list runs out.
+    Attempts(Vec<u64>),
+}
+
+impl Backoff {
+    /// Get the delay before retrying after the given (1-based) attempt failed
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let secs = match self {
+            Backoff::Fixed(secs) => *secs,
+            Backoff::Attempts(delays) => {
+                let index = (attempt.max(1) as usize - 1).min(delays.len().saturating_sub(1));
+                delays.get(index).copied().unwrap_or(0)
+            }
+        };
+        Duration::from_secs(secs)
+    }
+}
+
+impl From<u64> for Backoff {
+    fn from(secs: u64) -> Self {
+        Backoff::Fixed(secs)
+    }
+}
+
+impl From<Vec<u64>> for Backoff {
+    fn from(delays: Vec<u64>) -> Self {
+        Backoff::Attempts(delays)
+    }
+}
+
+impl<const N: usize> From<[u64; N]> for Backoff {
+    fn from(delays: [u64; N]) -> Self {
+        Backoff::Attempts(delays.to_vec())
+    }
+}
 
 /// A trait that represents a job that can be queued and processed
 #[async_trait]
@@ -20,9 +69,44 @@ pub trait Job: Send + Sync {
         None
     }
 
+    /// How long to wait before retrying the job after a failed attempt
+    fn backoff(&self) -> Option<Backoff> {
+        None
+    }
+
+    /// The number of seconds the job may run before it is cancelled and counted as failed
+    fn timeout(&self) -> Option<u64> {
+        None
+    }
+
+    /// Middleware to run around the job
+    fn middleware(&self) -> Vec<Box<dyn JobMiddleware>> {
+        Vec::new()
+    }
+
+    /// An id that makes the job unique: while a job with the same type and id is queued,
+    /// dispatching another one fails with `QueueError::AlreadyQueued`
+    fn unique_id(&self) -> Option<String> {
+        None
+    }
+
+    /// The number of seconds a unique job holds its lock at most. By default it's held
+    /// until the job has been processed.
+    fn unique_for(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the job's data is encrypted with the application key while it is queued
+    fn encrypted(&self) -> bool {
+        false
+    }
+
     /// Handle the job
     async fn handle(&self) -> Result<(), Box<dyn std::error::Error>>;
 
+    /// Called once the job has failed for good, e.g. to notify someone or undo partial work
+    async fn failed(&self, _error: &str) {}
+
     /// Serialize the job to a string
     fn serialize(&self) -> Result<String, Box<dyn std::error::Error>>;
 
@@ -35,18 +119,160 @@ pub trait Job: Send + Sync {
 
 /// A wrapper around a job that includes metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct JobWrapper {
+pub struct JobWrapper {
     /// The type name of the job
     pub job_type: String,
-    /// The serialized job data
+    /// The serialized job data, encrypted if `encrypted` is set
     pub job_data: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// Jobs to dispatch, in order, once this one succeeds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<PendingJob>,
+    /// The batch this job belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<Uuid>,
 }
 
 impl JobWrapper {
     pub fn new<T: Job>(job: &T) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut job_data = job.serialize()?;
+        if job.encrypted() {
+            job_data = Crypt::encrypt_string(&job_data)?;
+        }
         Ok(Self {
             job_type: T::type_name(),
-            job_data: job.serialize()?,
+            job_data,
+            encrypted: job.encrypted(),
+            chain: Vec::new(),
+            batch_id: None,
         })
     }
-} 
\ No newline at end of file
+
+    /// The serialized job data, decrypted if needed
+    pub fn data(&self) -> Result<String, Box<dyn std::error::Error>> {
+        if self.encrypted {
+            Ok(Crypt::decrypt_string(&self.job_data)?)
+        } else {
+            Ok(self.job_data.clone())
+        }
+    }
+}
+
+/// The lock a unique job takes in the cache when it is dispatched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UniqueLock {
+    pub key: String,
+    pub ttl: Option<u64>,
+}
+
+/// A serialized job together with where and when it should be pushed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingJob {
+    pub queue: String,
+    pub delay: Option<u64>,
+    pub job: JobWrapper,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique_lock: Option<UniqueLock>,
+}
+
+impl PendingJob {
+    pub fn new<T: Job>(job: &T) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            queue: job.queue().unwrap_or_else(|| "default".to_string()),
+            delay: job.delay(),
+            job: JobWrapper::new(job)?,
+            unique_lock: job.unique_id().map(|id| UniqueLock {
+                key: unique_key(&T::type_name(), &id),
+                ttl: job.unique_for(),
+            }),
+        })
+    }
+
+    /// Push the job onto its queue
+    pub async fn push(self) -> Result<Uuid, Box<dyn std::error::Error>> {
+        if let Some(lock) = &self.unique_lock {
+            let store = Cache::store();
+            let store = store.read().await;
+            let ttl = lock.ttl.map(Duration::from_secs);
+            if !store.add(&lock.key, serde_json::Value::Bool(true), ttl).await {
+                return Err(QueueError::AlreadyQueued(self.job.job_type).into());
+            }
+        }
+
+        let payload = serde_json::to_string(&self.job)?;
+        let result = Queue::push(&self.queue, payload, self.delay.map(Duration::from_secs))
+            .await
+            .map_err(|e| e.to_string());
+        if let (Err(_), Some(lock)) = (&result, &self.unique_lock) {
+            Cache::forget(&lock.key).await;
+        }
+        Ok(result?)
+    }
+}
+
+/// A job of any type that can be dispatched, so different jobs can be chained or batched together
+pub trait Dispatchable: Send + Sync {
+    fn pending(&self) -> Result<PendingJob, Box<dyn std::error::Error>>;
+}
+
+impl<T: Job> Dispatchable for T {
+    fn pending(&self) -> Result<PendingJob, Box<dyn std::error::Error>> {
+        PendingJob::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::queue::job;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SyncCustomer {
+        customer: String,
+    }
+
+    #[job]
+    #[async_trait]
+    impl Job for SyncCustomer {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn unique_id(&self) -> Option<String> {
+            Some(self.customer.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unique_jobs_are_refused_while_one_is_queued() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        let job = || SyncCustomer { customer: "customer-1".to_string() };
+
+        let id = Queue::dispatch(job()).await.unwrap();
+        let error = Queue::dispatch(job()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueueError>(), Some(QueueError::AlreadyQueued(_))));
+        Queue::dispatch(SyncCustomer { customer: "customer-2".to_string() }).await.unwrap();
+        Queue::assert_pushed_times::<SyncCustomer>(2);
+
+        // The worker releases the lock once the job is done, after which it can be dispatched again
+        Queue::delete(id).await.unwrap();
+        Cache::forget(&unique_key("SyncCustomer", "customer-1")).await;
+        Queue::dispatch(job()).await.unwrap();
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        assert_eq!(Backoff::Fixed(30).delay(1), Duration::from_secs(30));
+        assert_eq!(Backoff::Fixed(30).delay(4), Duration::from_secs(30));
+
+        let backoff = Backoff::from([10, 60, 300]);
+        assert_eq!(backoff.delay(1), Duration::from_secs(10));
+        assert_eq!(backoff.delay(2), Duration::from_secs(60));
+        assert_eq!(backoff.delay(3), Duration::from_secs(300));
+        assert_eq!(backoff.delay(7), Duration::from_secs(300));
+
+        assert_eq!(Backoff::Attempts(vec![]).delay(1), Duration::ZERO);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::framework::cache::Cache;
+use chrono::Utc;
+use std::time::Duration;
+
+/// Number of minutes the throughput is averaged over
+const WINDOW_MINUTES: i64 = 5;
+
+/// How long each per-minute counter is kept
+const COUNTER_TTL: Duration = Duration::from_secs(3600);
+
+fn counter_key(queue: &str, outcome: &str, minute: i64) -> String {
+    format!("queue-metrics:{}:{}:{}", queue, outcome, minute)
+}
+
+async fn record(queue: &str, outcome: &str) {
+    if !Cache::is_initialized() {
+        return;
+    }
+    let key = counter_key(queue, outcome, Utc::now().timestamp() / 60);
+    // Seed the counter so it expires once it's too old to be reported
+    Cache::add(&key, 0, COUNTER_TTL).await;
+    Cache::increment(&key, 1).await;
+}
+
+/// Count a job that completed successfully
+pub async fn record_processed(queue: &str) {
+    record(queue, "processed").await;
+}
+
+/// Count a job that failed for good
+pub async fn record_failed(queue: &str) {
+    record(queue, "failed").await;
+}
+
+/// Jobs processed and failed per minute in the queue, averaged over the last few minutes
+pub async fn throughput(queue: &str) -> (f64, f64) {
+    if !Cache::is_initialized() {
+        return (0.0, 0.0);
+    }
+
+    let now = Utc::now().timestamp() / 60;
+    let mut processed = 0;
+    let mut failed = 0;
+    for minute in (now - WINDOW_MINUTES + 1)..=now {
+        processed += Cache::get::<i64>(&counter_key(queue, "processed", minute)).await.unwrap_or(0);
+        failed += Cache::get::<i64>(&counter_key(queue, "failed", minute)).await.unwrap_or(0);
+    }
+    (
+        processed as f64 / WINDOW_MINUTES as f64,
+        failed as f64 / WINDOW_MINUTES as f64,
+    )
+}
@@ -0,0 +1,240 @@
+use crate::framework::cache::{Cache, Lock};
+use crate::framework::queue::QueuedJob;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+static LIMITS: Lazy<RwLock<HashMap<String, Limit>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// What the worker should do with a job after a middleware has looked at it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Run the job
+    Run,
+    /// Put the job back on the queue without running it
+    Release(Duration),
+}
+
+/// Wraps the handling of a job, returned from `Job::middleware`
+#[async_trait]
+pub trait JobMiddleware: Send + Sync {
+    /// Called before the job runs
+    async fn before(&self, job: &QueuedJob) -> Action;
+
+    /// Called after the job ran, whether or not it succeeded
+    async fn after(&self, _job: &QueuedJob) {}
+}
+
+/// The cache key held while a unique job is queued
+pub(crate) fn unique_key(job_type: &str, unique_id: &str) -> String {
+    format!("queue-unique:{}:{}", job_type, unique_id)
+}
+
+/// Prevents jobs that share a key from running at the same time
+pub struct WithoutOverlapping {
+    key: String,
+    lock: Mutex<Option<Lock>>,
+    release_after: Duration,
+    expires_after: Duration,
+}
+
+impl WithoutOverlapping {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: format!("queue-overlap:{}", key.into()),
+            lock: Mutex::new(None),
+            release_after: Duration::from_secs(5),
+            expires_after: Duration::from_secs(300),
+        }
+    }
+
+    /// How long to wait before trying a job again when another one holds the key
+    pub fn release_after(mut self, delay: Duration) -> Self {
+        self.release_after = delay;
+        self
+    }
+
+    /// How long the lock is held at most, in case a worker dies while running the job
+    pub fn expires_after(mut self, ttl: Duration) -> Self {
+        self.expires_after = ttl;
+        self
+    }
+}
+
+#[async_trait]
+impl JobMiddleware for WithoutOverlapping {
+    async fn before(&self, _job: &QueuedJob) -> Action {
+        let lock = Cache::lock(&self.key, self.expires_after);
+        if lock.get().await {
+            *self.lock.lock().unwrap() = Some(lock);
+            Action::Run
+        } else {
+            Action::Release(self.release_after)
+        }
+    }
+
+    async fn after(&self, _job: &QueuedJob) {
+        let lock = self.lock.lock().unwrap().take();
+        if let Some(lock) = lock {
+            lock.release().await;
+        }
+    }
+}
+
+/// How many jobs may run within a window of time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limit {
+    pub max_attempts: u32,
+    pub decay: Duration,
+}
+
+impl Limit {
+    pub fn new(max_attempts: u32, decay: Duration) -> Self {
+        Self { max_attempts, decay }
+    }
+
+    pub fn per_second(max_attempts: u32) -> Self {
+        Self::new(max_attempts, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(max_attempts: u32) -> Self {
+        Self::new(max_attempts, Duration::from_secs(60))
+    }
+
+    pub fn per_hour(max_attempts: u32) -> Self {
+        Self::new(max_attempts, Duration::from_secs(3600))
+    }
+
+    pub fn per_day(max_attempts: u32) -> Self {
+        Self::new(max_attempts, Duration::from_secs(86400))
+    }
+}
+
+/// Named limits used by the `RateLimited` job middleware
+pub struct RateLimiter;
+
+impl RateLimiter {
+    /// Define a named limit, e.g. `RateLimiter::define("emails", Limit::per_minute(30))`
+    pub fn define(name: &str, limit: Limit) {
+        LIMITS.write().unwrap().insert(name.to_string(), limit);
+    }
+
+    /// Get a named limit
+    pub fn limit(name: &str) -> Option<Limit> {
+        LIMITS.read().unwrap().get(name).cloned()
+    }
+
+    /// Count a hit against a limit, returning how long to wait if it was already exhausted
+    pub async fn hit(key: &str, limit: &Limit) -> Option<Duration> {
+        let window = limit.decay.as_secs().max(1);
+        let now = chrono::Utc::now().timestamp() as u64;
+        let window_start = now - now % window;
+        let counter = format!("queue-limit:{}:{}", key, window_start);
+
+        // Create the counter with an expiry first, since incrementing alone would keep it forever
+        Cache::add(&counter, 0, Duration::from_secs(window)).await;
+        let hits = Cache::increment(&counter, 1).await;
+
+        if hits > limit.max_attempts as i64 {
+            Some(Duration::from_secs(window_start + window - now))
+        } else {
+            None
+        }
+    }
+}
+
+/// Releases a job back onto the queue when a named limit has been used up
+pub struct RateLimited {
+    name: String,
+    by: Option<String>,
+}
+
+impl RateLimited {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            by: None,
+        }
+    }
+
+    /// Keep a separate count per key, e.g. per customer
+    pub fn by(mut self, key: impl Into<String>) -> Self {
+        self.by = Some(key.into());
+        self
+    }
+}
+
+#[async_trait]
+impl JobMiddleware for RateLimited {
+    async fn before(&self, _job: &QueuedJob) -> Action {
+        let limit = match RateLimiter::limit(&self.name) {
+            Some(limit) => limit,
+            None => {
+                eprintln!("Rate limiter {} is not defined, running the job without a limit", self.name);
+                return Action::Run;
+            }
+        };
+
+        let key = match &self.by {
+            Some(by) => format!("{}:{}", self.name, by),
+            None => self.name.clone(),
+        };
+        match RateLimiter::hit(&key, &limit).await {
+            Some(delay) => Action::Release(delay),
+            None => Action::Run,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn queued_job() -> QueuedJob {
+        QueuedJob {
+            id: Uuid::new_v4(),
+            queue: "default".to_string(),
+            payload: "{}".to_string(),
+            attempts: 0,
+            reserved_at: None,
+            available_at: chrono::Utc::now(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_without_overlapping_releases_an_overlapping_job() {
+        let _fake = Cache::fake();
+        let first = WithoutOverlapping::new("order-1");
+        let second = WithoutOverlapping::new("order-1").release_after(Duration::from_secs(30));
+
+        assert_eq!(first.before(&queued_job()).await, Action::Run);
+        assert_eq!(second.before(&queued_job()).await, Action::Release(Duration::from_secs(30)));
+        assert_eq!(WithoutOverlapping::new("order-2").before(&queued_job()).await, Action::Run);
+
+        first.after(&queued_job()).await;
+        assert_eq!(second.before(&queued_job()).await, Action::Run);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_releases_once_the_limit_is_used_up() {
+        let _fake = Cache::fake();
+        // A daily window, so the count doesn't reset while the test runs
+        RateLimiter::define("invoices", Limit::per_day(2));
+        let limited = RateLimited::new("invoices").by("customer-1");
+
+        assert_eq!(limited.before(&queued_job()).await, Action::Run);
+        assert_eq!(limited.before(&queued_job()).await, Action::Run);
+        match limited.before(&queued_job()).await {
+            Action::Release(delay) => assert!(delay > Duration::ZERO && delay <= Duration::from_secs(86400)),
+            Action::Run => panic!("Expected the job to be released once the limit was used up"),
+        }
+
+        let other = RateLimited::new("invoices").by("customer-2");
+        assert_eq!(other.before(&queued_job()).await, Action::Run);
+        assert_eq!(RateLimited::new("undefined").before(&queued_job()).await, Action::Run);
+    }
+}
@@ -11,8 +11,19 @@ pub mod drivers;
 pub mod config;
 pub mod worker;
 pub mod job;
+pub mod batch;
+pub mod error;
+pub mod middleware;
+pub mod fake;
+pub mod metrics;
+pub mod dashboard;
+pub mod events;
 
-pub use job::Job;
+pub use job::{Backoff, Dispatchable, Job};
+pub use batch::{Batch, PendingBatch};
+pub use error::QueueError;
+pub use events::JobEvent;
+pub use ruskit_macros::job;
 
 static QUEUE_DRIVER: OnceCell<Arc<RwLock<Box<dyn QueueDriver + Send + Sync>>>> = OnceCell::new();
 
@@ -27,6 +38,36 @@ pub struct QueuedJob {
     pub created_at: DateTime<Utc>,
 }
 
+/// A job that exhausted its attempts and was moved out of the queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedJob {
+    pub id: Uuid,
+    pub queue: String,
+    pub payload: String,
+    pub exception: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// A snapshot of the state of a single queue
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub queue: String,
+    /// Jobs ready to be picked up
+    pub pending: u64,
+    /// Jobs currently held by a worker
+    pub reserved: u64,
+    /// Jobs waiting for their delay to pass
+    pub delayed: u64,
+    /// Jobs that failed for good
+    pub failed: u64,
+    /// Seconds the oldest pending job has been waiting
+    pub oldest_pending_seconds: Option<u64>,
+    /// Jobs completed per minute, averaged over the last few minutes
+    pub processed_per_minute: f64,
+    /// Jobs failed per minute, averaged over the last few minutes
+    pub failed_per_minute: f64,
+}
+
 #[async_trait]
 pub trait QueueDriver {
     /// Push a new job onto the queue
@@ -40,12 +81,68 @@ pub trait QueueDriver {
     
     /// Release a job back onto the queue
     async fn release(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Release a job back onto the queue without counting an attempt, for jobs a middleware held
+    /// back before they ran. Drivers that count attempts when a job is received count it anyway.
+    async fn release_without_attempt(&self, id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        self.release(id, delay).await
+    }
     
     /// Get the size of the queue
     async fn size(&self, queue: &str) -> Result<u64, Box<dyn std::error::Error>>;
     
     /// Clear all jobs from the queue
     async fn clear(&self, queue: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Remove a job from the queue and record it as failed
+    async fn fail(&self, _job: &QueuedJob, _exception: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Failed jobs are not supported by this queue driver".into())
+    }
+
+    /// Get all failed jobs, most recent first
+    async fn failed_jobs(&self) -> Result<Vec<FailedJob>, Box<dyn std::error::Error>> {
+        Err("Failed jobs are not supported by this queue driver".into())
+    }
+
+    /// Find a failed job by its id
+    async fn find_failed(&self, _id: Uuid) -> Result<Option<FailedJob>, Box<dyn std::error::Error>> {
+        Err("Failed jobs are not supported by this queue driver".into())
+    }
+
+    /// Delete a failed job, returning whether it existed
+    async fn forget_failed(&self, _id: Uuid) -> Result<bool, Box<dyn std::error::Error>> {
+        Err("Failed jobs are not supported by this queue driver".into())
+    }
+
+    /// Delete all failed jobs, returning how many were removed
+    async fn flush_failed(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        Err("Failed jobs are not supported by this queue driver".into())
+    }
+
+    /// Push a failed job back onto its original queue and delete the failed record, returning
+    /// the new job's id or `None` if no failed job has that id.
+    ///
+    /// Drivers that can't do both in one step push first, so a failure in between leaves the
+    /// job queued and still listed as failed rather than losing it. Retrying it again would
+    /// then run it twice.
+    async fn retry_failed(&self, id: Uuid) -> Result<Option<Uuid>, Box<dyn std::error::Error>> {
+        let Some(failed) = self.find_failed(id).await? else {
+            return Ok(None);
+        };
+        let new_id = self.push(&failed.queue, failed.payload, None).await?;
+        self.forget_failed(id).await?;
+        Ok(Some(new_id))
+    }
+
+    /// Count the jobs in the queue by state. Drivers that can't tell the states apart report
+    /// every job as pending.
+    async fn stats(&self, queue: &str) -> Result<QueueStats, Box<dyn std::error::Error>> {
+        Ok(QueueStats {
+            queue: queue.to_string(),
+            pending: self.size(queue).await?,
+            ..Default::default()
+        })
+    }
 }
 
 /// A Laravel-like Queue facade for easy job queueing
@@ -54,6 +151,10 @@ pub struct Queue;
 impl Queue {
     /// Get the queue driver instance
     pub fn driver() -> Arc<RwLock<Box<dyn QueueDriver + Send + Sync>>> {
+        if let Some(fake) = fake::current() {
+            return fake;
+        }
+
         QUEUE_DRIVER
             .get()
             .expect("Queue driver not initialized")
@@ -88,6 +189,13 @@ impl Queue {
         driver.release(id, delay).await
     }
 
+    /// Release a job back onto the queue without counting an attempt
+    pub async fn release_without_attempt(id: Uuid, delay: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver.release_without_attempt(id, delay).await
+    }
+
     /// Get the size of the queue
     pub async fn size(queue: &str) -> Result<u64, Box<dyn std::error::Error>> {
         let driver = Self::driver();
@@ -102,13 +210,94 @@ impl Queue {
         driver.clear(queue).await
     }
 
+    /// Remove a job from the queue and record it as failed
+    pub async fn fail(job: &QueuedJob, exception: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver.fail(job, exception).await
+    }
+
+    /// Get all failed jobs, most recent first
+    pub async fn failed_jobs() -> Result<Vec<FailedJob>, Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver.failed_jobs().await
+    }
+
+    /// Push a failed job back onto its original queue
+    pub async fn retry(id: Uuid) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver
+            .retry_failed(id)
+            .await?
+            .ok_or_else(|| format!("No failed job found with id {}", id).into())
+    }
+
+    /// Push every failed job back onto its original queue
+    pub async fn retry_all() -> Result<Vec<Uuid>, Box<dyn std::error::Error>> {
+        let mut ids = Vec::new();
+        for failed in Self::failed_jobs().await? {
+            ids.push(Self::retry(failed.id).await?);
+        }
+        Ok(ids)
+    }
+
+    /// Delete a failed job
+    pub async fn forget_failed(id: Uuid) -> Result<bool, Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver.forget_failed(id).await
+    }
+
+    /// Delete all failed jobs
+    pub async fn flush_failed() -> Result<u64, Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        driver.flush_failed().await
+    }
+
+    /// Get the state of a queue, including its recent throughput
+    pub async fn stats(queue: &str) -> Result<QueueStats, Box<dyn std::error::Error>> {
+        let driver = Self::driver();
+        let driver = driver.read().await;
+        let mut stats = driver.stats(queue).await?;
+        let (processed, failed) = metrics::throughput(queue).await;
+        stats.processed_per_minute = processed;
+        stats.failed_per_minute = failed;
+        Ok(stats)
+    }
+
     /// Dispatch a job to the queue
     pub async fn dispatch<T: Job>(job: T) -> Result<Uuid, Box<dyn std::error::Error>> {
-        let queue = job.queue().unwrap_or_else(|| "default".to_string());
-        let delay = job.delay().map(|secs| Duration::from_secs(secs));
-        let wrapper = job::JobWrapper::new(&job)?;
-        let payload = serde_json::to_string(&wrapper)?;
-        Self::push(&queue, payload, delay).await
+        job::PendingJob::new(&job)?.push().await
+    }
+
+    /// Dispatch jobs that run one after another, each only once the previous one succeeded
+    pub async fn chain(jobs: Vec<Box<dyn Dispatchable>>) -> Result<Uuid, Box<dyn std::error::Error>> {
+        let mut jobs = jobs
+            .iter()
+            .map(|job| job.pending())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+        let mut first = jobs.next().ok_or("Cannot dispatch an empty chain")?;
+        first.job.chain = jobs.collect();
+        first.push().await
+    }
+
+    /// Start building a batch of jobs that is tracked as a whole
+    pub fn batch(jobs: Vec<Box<dyn Dispatchable>>) -> PendingBatch {
+        PendingBatch::new(jobs)
+    }
+
+    /// Find a batch by its id
+    pub async fn find_batch(id: Uuid) -> Result<Option<Batch>, Box<dyn std::error::Error>> {
+        batch::repository()?.find(id).await
+    }
+
+    /// Cancel a batch so that its remaining jobs are skipped
+    pub async fn cancel_batch(id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        batch::repository()?.cancel(id).await
     }
 
     /// Create a new worker for processing jobs
@@ -1,31 +1,67 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 use tokio::time::sleep;
-use crate::framework::queue::{Queue, job::JobWrapper};
-use std::sync::atomic::{AtomicBool, Ordering};
+use futures_util::future::join_all;
+use crate::framework::cache::Cache;
+use crate::framework::queue::{Queue, QueuedJob, Job, batch, events, metrics, job::{Backoff, JobWrapper}};
+use crate::framework::queue::middleware::{unique_key, Action};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::collections::HashMap;
 use once_cell::sync::Lazy;
-use tokio::sync::RwLock;
 
-type JobFactory = Box<dyn Fn(&str) -> Result<Box<dyn crate::framework::queue::Job>, Box<dyn std::error::Error>> + Send + Sync>;
+type JobFactory = fn(&str) -> Result<Box<dyn Job>, Box<dyn std::error::Error>>;
 
-static JOB_FACTORIES: Lazy<Arc<RwLock<HashMap<String, JobFactory>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(HashMap::new()))
+/// A job type the worker can run, submitted by the `#[job]` attribute
+pub struct JobRegistration {
+    pub job_type: fn() -> String,
+    pub deserialize: JobFactory,
+}
+
+inventory::collect!(JobRegistration);
+
+static JOB_FACTORIES: Lazy<HashMap<String, JobFactory>> = Lazy::new(|| {
+    inventory::iter::<JobRegistration>
+        .into_iter()
+        .map(|registration| ((registration.job_type)(), registration.deserialize))
+        .collect()
 });
 
 pub struct Worker {
-    queue: String,
+    queues: Vec<String>,
     sleep_duration: Duration,
     max_tries: u32,
+    backoff: Backoff,
+    timeout: Option<Duration>,
+    concurrency: usize,
+    max_jobs: Option<u64>,
+    max_time: Option<Duration>,
+    stop_when_empty: bool,
+    started_jobs: AtomicU64,
+    wake: Notify,
     pub running: Arc<AtomicBool>,
 }
 
 impl Worker {
+    /// Create a worker for a queue, or for several comma-separated queues in priority order
     pub fn new(queue: &str) -> Self {
         Self {
-            queue: queue.to_string(),
+            queues: queue
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
             sleep_duration: Duration::from_secs(1),
             max_tries: 3,
+            backoff: Backoff::Fixed(30),
+            timeout: None,
+            concurrency: 1,
+            max_jobs: None,
+            max_time: None,
+            stop_when_empty: false,
+            started_jobs: AtomicU64::new(0),
+            wake: Notify::new(),
             running: Arc::new(AtomicBool::new(true)),
         }
     }
@@ -35,89 +71,617 @@ impl Worker {
         self
     }
 
+    /// Set the number of attempts for jobs that don't specify their own `tries`
     pub fn max_tries(mut self, tries: u32) -> Self {
         self.max_tries = tries;
         self
     }
 
+    /// Set the retry delay for jobs that don't specify their own `backoff`
+    pub fn backoff(mut self, backoff: impl Into<Backoff>) -> Self {
+        self.backoff = backoff.into();
+        self
+    }
+
+    /// Set the time limit for jobs that don't specify their own `timeout`. Without one
+    /// those jobs may run for as long as they need.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how many jobs are processed at the same time
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Stop after this many jobs have been processed
+    pub fn max_jobs(mut self, max_jobs: u64) -> Self {
+        self.max_jobs = Some(max_jobs);
+        self
+    }
+
+    /// Stop once the worker has run for this long, after finishing the jobs in progress
+    pub fn max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Stop once every queue is empty instead of waiting for new jobs
+    pub fn stop_when_empty(mut self) -> Self {
+        self.stop_when_empty = true;
+        self
+    }
+
+    /// Process jobs until the worker is stopped or one of its limits is reached
     pub async fn run(&self) {
-        println!("Starting queue worker for queue: {}", self.queue);
-        
+        println!(
+            "Starting queue worker for queue: {} (concurrency {})",
+            self.queues.join(","),
+            self.concurrency
+        );
+
+        let started = Instant::now();
+        join_all((0..self.concurrency).map(|_| self.work(started))).await;
+
+        println!("Queue worker stopped after {} jobs", self.started_jobs.load(Ordering::SeqCst));
+    }
+
+    /// Run the worker until it receives SIGTERM or Ctrl+C, then let the jobs in progress finish
+    pub async fn run_until_signal(&self) {
+        self.run_until(shutdown_signal()).await;
+    }
+
+    /// Run the worker until the shutdown future completes, then let the jobs in progress finish
+    pub async fn run_until(&self, shutdown: impl std::future::Future<Output = ()>) {
+        let run = self.run();
+        tokio::pin!(run);
+
+        tokio::select! {
+            _ = &mut run => return,
+            _ = shutdown => {
+                println!("\nReceived shutdown signal, finishing jobs in progress...");
+                self.stop();
+            }
+        }
+        run.await;
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.wake.notify_waiters();
+    }
+
+    /// A single processing loop; the worker runs one per unit of concurrency
+    async fn work(&self, started: Instant) {
         while self.running.load(Ordering::SeqCst) {
-            match Queue::pop(&self.queue).await {
-                Some(queued_job) => {
-                    println!("Processing job {} from queue {}", queued_job.id, queued_job.queue);
-                    
-                    // Check if we should process this job based on attempts
-                    if queued_job.attempts > self.max_tries {
-                        println!("Job {} has exceeded maximum attempts ({}), deleting", queued_job.id, self.max_tries);
-                        if let Err(e) = Queue::delete(queued_job.id).await {
-                            eprintln!("Failed to delete failed job {}: {}", queued_job.id, e);
-                        }
-                        continue;
+            if self.max_time.is_some_and(|max_time| started.elapsed() >= max_time) {
+                break;
+            }
+
+            // Reserve a slot before popping so concurrent loops never run more than max_jobs
+            let slot = self.started_jobs.fetch_add(1, Ordering::SeqCst);
+            if self.max_jobs.is_some_and(|max_jobs| slot >= max_jobs) {
+                self.started_jobs.fetch_sub(1, Ordering::SeqCst);
+                break;
+            }
+
+            match self.next_job().await {
+                Some(queued_job) => self.process(queued_job).await,
+                None => {
+                    self.started_jobs.fetch_sub(1, Ordering::SeqCst);
+                    if self.stop_when_empty {
+                        break;
                     }
 
-                    // Process the job
-                    match serde_json::from_str::<JobWrapper>(&queued_job.payload) {
-                        Ok(wrapper) => {
-                            match process_job(&wrapper).await {
-                                Ok(_) => {
-                                    println!("Job {} completed successfully", queued_job.id);
-                                    if let Err(e) = Queue::delete(queued_job.id).await {
-                                        eprintln!("Failed to delete completed job {}: {}", queued_job.id, e);
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("Job {} failed: {}", queued_job.id, e);
-                                    // Release the job back to the queue with a delay
-                                    if let Err(e) = Queue::release(queued_job.id, Some(Duration::from_secs(30))).await {
-                                        eprintln!("Failed to release failed job {}: {}", queued_job.id, e);
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to deserialize job payload: {}", e);
-                            if let Err(e) = Queue::delete(queued_job.id).await {
-                                eprintln!("Failed to delete malformed job {}: {}", queued_job.id, e);
-                            }
-                        }
+                    // No jobs available, sleep before checking again unless the worker is stopped
+                    tokio::select! {
+                        _ = sleep(self.sleep_duration) => {}
+                        _ = self.wake.notified() => {}
                     }
                 }
-                None => {
-                    // No jobs available, sleep before checking again
-                    sleep(self.sleep_duration).await;
+            }
+        }
+    }
+
+    /// Pop the next job, trying each queue in priority order
+    async fn next_job(&self) -> Option<QueuedJob> {
+        for queue in &self.queues {
+            if let Some(job) = Queue::pop(queue).await {
+                return Some(job);
+            }
+        }
+        None
+    }
+
+    /// Run a single reserved job and delete, release or fail it depending on the outcome
+    async fn process(&self, queued_job: QueuedJob) {
+        println!("Processing job {} from queue {}", queued_job.id, queued_job.queue);
+
+        let (wrapper, job) = match resolve_job(&queued_job.payload) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                eprintln!("Failed to deserialize job payload: {}", e);
+                let exception = format!("Malformed job payload: {}", e);
+                fail_job(&queued_job, &exception).await;
+                events::job_failed(&queued_job, None, &exception);
+                return;
+            }
+        };
+
+        // Jobs of a cancelled batch are skipped, and counted apart so the batch can still finish
+        if let Some(batch_id) = wrapper.batch_id {
+            if batch::is_cancelled(batch_id).await {
+                println!("Skipping job {} because batch {} was cancelled", queued_job.id, batch_id);
+                if let Err(e) = Queue::delete(queued_job.id).await {
+                    eprintln!("Failed to delete skipped job {}: {}", queued_job.id, e);
+                }
+                release_unique_lock(&wrapper, job.as_ref()).await;
+                batch::job_skipped(batch_id).await;
+                return;
+            }
+        }
+
+        // Check if we should process this job based on attempts
+        let max_tries = job.tries().unwrap_or(self.max_tries);
+        if queued_job.attempts >= max_tries {
+            println!("Job {} has exceeded maximum attempts ({}), marking as failed", queued_job.id, max_tries);
+            let exception = "Job has been attempted too many times";
+            release_unique_lock(&wrapper, job.as_ref()).await;
+            fail_job(&queued_job, exception).await;
+            failed(&queued_job, &wrapper, job.as_ref(), exception).await;
+            return;
+        }
+
+        let middleware = job.middleware();
+        for (index, layer) in middleware.iter().enumerate() {
+            if let Action::Release(delay) = layer.before(&queued_job).await {
+                for passed in middleware[..index].iter().rev() {
+                    passed.after(&queued_job).await;
+                }
+                println!("Job {} released by middleware for {} seconds", queued_job.id, delay.as_secs());
+                // The job didn't run, so it doesn't use up one of its tries
+                if let Err(e) = Queue::release_without_attempt(queued_job.id, Some(delay)).await {
+                    eprintln!("Failed to release job {}: {}", queued_job.id, e);
+                }
+                return;
+            }
+        }
+
+        events::job_processing(&queued_job, &wrapper.job_type);
+        let result = self.run_job(job.as_ref()).await.map_err(|e| e.to_string());
+        for layer in middleware.iter().rev() {
+            layer.after(&queued_job).await;
+        }
+
+        match result {
+            Ok(_) => {
+                println!("Job {} completed successfully", queued_job.id);
+                if let Err(e) = Queue::delete(queued_job.id).await {
+                    eprintln!("Failed to delete completed job {}: {}", queued_job.id, e);
+                }
+                release_unique_lock(&wrapper, job.as_ref()).await;
+                events::job_processed(&queued_job, &wrapper.job_type);
+                completed(&queued_job, wrapper).await;
+            }
+            Err(error) => {
+                eprintln!("Job {} failed: {}", queued_job.id, error);
+                events::job_exception_occurred(&queued_job, &wrapper.job_type, &error);
+                let attempt = queued_job.attempts + 1;
+                if attempt >= max_tries {
+                    release_unique_lock(&wrapper, job.as_ref()).await;
+                    fail_job(&queued_job, &error).await;
+                    failed(&queued_job, &wrapper, job.as_ref(), &error).await;
+                } else {
+                    // Release the job back to the queue with a delay
+                    let delay = job.backoff().unwrap_or_else(|| self.backoff.clone()).delay(attempt);
+                    if let Err(e) = Queue::release(queued_job.id, Some(delay)).await {
+                        eprintln!("Failed to release failed job {}: {}", queued_job.id, e);
+                    }
                 }
             }
         }
     }
 
-    pub fn stop(&self) {
-        self.running.store(false, Ordering::SeqCst);
+    /// Handle the job, cancelling it once it runs longer than its timeout, if it has one
+    async fn run_job(&self, job: &dyn Job) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(timeout) = job.timeout().map(Duration::from_secs).or(self.timeout) else {
+            return job.handle().await;
+        };
+        match tokio::time::timeout(timeout, job.handle()).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("Job timed out after {} seconds", timeout.as_secs()).into()),
+        }
+    }
+}
+
+/// Wait for SIGTERM or Ctrl+C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Move a job to the failed jobs store so it can be inspected and retried later
+async fn fail_job(queued_job: &QueuedJob, exception: &str) {
+    if let Err(e) = Queue::fail(queued_job, exception).await {
+        eprintln!("Failed to record failed job {}: {}", queued_job.id, e);
+    }
+}
+
+/// Let another copy of a unique job be dispatched once this one is done
+async fn release_unique_lock(wrapper: &JobWrapper, job: &dyn Job) {
+    if let Some(id) = job.unique_id() {
+        Cache::forget(&unique_key(&wrapper.job_type, &id)).await;
+    }
+}
+
+/// Let a job that failed for good clean up, dropping the rest of its chain and updating its batch
+async fn failed(queued_job: &QueuedJob, wrapper: &JobWrapper, job: &dyn Job, exception: &str) {
+    job.failed(exception).await;
+    events::job_failed(queued_job, Some(&wrapper.job_type), exception);
+    metrics::record_failed(&queued_job.queue).await;
+    if let Some(batch_id) = wrapper.batch_id {
+        batch::job_failed(batch_id, queued_job.id).await;
+    }
+}
+
+/// Dispatch the next job of the chain and update the batch of a job that succeeded
+async fn completed(queued_job: &QueuedJob, wrapper: JobWrapper) {
+    metrics::record_processed(&queued_job.queue).await;
+    let mut chain = wrapper.chain.into_iter();
+    if let Some(mut next) = chain.next() {
+        next.job.chain = chain.collect();
+        if let Err(e) = next.push().await {
+            eprintln!("Failed to dispatch the next job in the chain of {}: {}", queued_job.id, e);
+        }
+    }
+    if let Some(batch_id) = wrapper.batch_id {
+        batch::job_succeeded(batch_id).await;
+    }
+}
+
+/// Run a job straight away instead of queueing it, as the sync driver does. The job gets a
+/// single attempt and its error is returned to the caller.
+pub(crate) async fn run_inline(queued_job: &QueuedJob) -> Result<(), String> {
+    let (wrapper, job) = resolve_job(&queued_job.payload).map_err(|e| format!("Malformed job payload: {}", e))?;
+
+    events::job_processing(queued_job, &wrapper.job_type);
+    let result = job.handle().await.map_err(|e| e.to_string());
+    release_unique_lock(&wrapper, job.as_ref()).await;
+
+    match result {
+        Ok(_) => {
+            events::job_processed(queued_job, &wrapper.job_type);
+            completed(queued_job, wrapper).await;
+            Ok(())
+        }
+        Err(error) => {
+            events::job_exception_occurred(queued_job, &wrapper.job_type, &error);
+            failed(queued_job, &wrapper, job.as_ref(), &error).await;
+            Err(error)
+        }
     }
 }
 
-/// Register a job type for processing
-pub fn register_job<T: crate::framework::queue::Job + 'static>() {
-    let job_type = T::type_name();
-    let factory: JobFactory = Box::new(move |data| {
-        T::deserialize(data)
-    });
-
-    tokio::spawn(async move {
-        let mut factories = JOB_FACTORIES.write().await;
-        factories.insert(job_type, factory);
-    });
+/// Deserialize a queued payload into a runnable job using the registered job types
+fn resolve_job(payload: &str) -> Result<(JobWrapper, Box<dyn Job>), Box<dyn std::error::Error>> {
+    let wrapper: JobWrapper = serde_json::from_str(payload)?;
+
+    let job = match JOB_FACTORIES.get(&wrapper.job_type) {
+        Some(factory) => factory(&wrapper.data()?)?,
+        None => return Err(format!("No job factory registered for type: {}", wrapper.job_type).into()),
+    };
+    Ok((wrapper, job))
 }
 
-async fn process_job(wrapper: &JobWrapper) -> Result<(), Box<dyn std::error::Error>> {
-    let factories = JOB_FACTORIES.read().await;
-    
-    if let Some(factory) = factories.get(&wrapper.job_type) {
-        let job = factory(&wrapper.job_data)?;
-        job.handle().await?;
-        Ok(())
-    } else {
-        Err(format!("No job factory registered for type: {}", wrapper.job_type).into())
-    }
-} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::queue::job;
+    use crate::framework::queue::middleware::JobMiddleware;
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ResizeImage {
+        path: String,
+    }
+
+    #[job(queue = "images", tries = 5, backoff = [10, 60], timeout = 30)]
+    #[async_trait]
+    impl Job for ResizeImage {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_job_attribute_registers_job() {
+        let job = ResizeImage { path: "photo.png".to_string() };
+        let payload = serde_json::to_string(&JobWrapper::new(&job).unwrap()).unwrap();
+
+        let (_, resolved) = resolve_job(&payload).unwrap();
+        assert_eq!(resolved.queue(), Some("images".to_string()));
+        assert_eq!(resolved.tries(), Some(5));
+        assert_eq!(resolved.delay(), None);
+        assert_eq!(resolved.backoff(), Some(Backoff::Attempts(vec![10, 60])));
+        assert_eq!(resolved.timeout(), Some(30));
+        assert_eq!(ResizeImage::type_name(), "ResizeImage");
+
+        assert!(resolve_job(r#"{"job_type":"Unknown","job_data":"{}"}"#).is_err());
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ExportCustomer {
+        email: String,
+    }
+
+    #[job(encrypted)]
+    #[async_trait]
+    impl Job for ExportCustomer {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encrypted_jobs_are_decrypted_by_the_worker() {
+        use crate::framework::crypt::{Crypt, Encrypter};
+        let _ = Crypt::set_encrypter(Encrypter::new(&Encrypter::generate_key()).unwrap());
+
+        let job = ExportCustomer { email: "jane@example.com".to_string() };
+        let payload = serde_json::to_string(&JobWrapper::new(&job).unwrap()).unwrap();
+        assert!(!payload.contains("jane@example.com"));
+
+        let (wrapper, _) = resolve_job(&payload).unwrap();
+        assert!(wrapper.encrypted);
+        assert_eq!(wrapper.data().unwrap(), r#"{"email":"jane@example.com"}"#);
+    }
+
+    static FAILED_CALLS: Lazy<std::sync::Mutex<Vec<String>>> = Lazy::new(Default::default);
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ChargeCard {
+        order: String,
+    }
+
+    #[job(tries = 2)]
+    #[async_trait]
+    impl Job for ChargeCard {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Err("card declined".into())
+        }
+
+        async fn failed(&self, error: &str) {
+            FAILED_CALLS.lock().unwrap().push(format!("{}: {}", self.order, error));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_hooks_run_around_failed_attempts() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        let job = ChargeCard { order: "order-1".to_string() };
+        let mut queued_job = QueuedJob {
+            id: uuid::Uuid::new_v4(),
+            queue: "default".to_string(),
+            payload: serde_json::to_string(&JobWrapper::new(&job).unwrap()).unwrap(),
+            attempts: 0,
+            reserved_at: None,
+            available_at: chrono::Utc::now(),
+            created_at: chrono::Utc::now(),
+        };
+
+        // Hooks are global, so only record events for this job
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let events = events.clone();
+            let id = queued_job.id;
+            move |event: &events::JobEvent| {
+                if event.job.id == id {
+                    events.lock().unwrap().push(format!("{} {}", name, event.exception.unwrap_or("")).trim().to_string());
+                }
+            }
+        };
+        Queue::before(record("before"));
+        Queue::after(record("after"));
+        Queue::exception_occurred(record("exception"));
+        Queue::failing(record("failing"));
+
+        let worker = Worker::new("default");
+        worker.process(queued_job.clone()).await;
+        assert_eq!(*events.lock().unwrap(), vec!["before", "exception card declined"]);
+
+        queued_job.attempts = 1;
+        worker.process(queued_job).await;
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["before", "exception card declined", "before", "exception card declined", "failing card declined"]
+        );
+        assert_eq!(*FAILED_CALLS.lock().unwrap(), vec!["order-1: card declined"]);
+    }
+
+    static RUNS: Lazy<std::sync::Mutex<Vec<String>>> = Lazy::new(Default::default);
+
+    fn runs() -> Vec<String> {
+        RUNS.lock().unwrap().clone()
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendReport {
+        name: String,
+    }
+
+    #[job]
+    #[async_trait]
+    impl Job for SendReport {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            RUNS.lock().unwrap().push(self.name.clone());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ImportFeed {
+        name: String,
+    }
+
+    #[job]
+    #[async_trait]
+    impl Job for ImportFeed {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            RUNS.lock().unwrap().push(format!("{} started", self.name));
+            sleep(Duration::from_millis(200)).await;
+            RUNS.lock().unwrap().push(format!("{} finished", self.name));
+            Ok(())
+        }
+    }
+
+    struct HoldBack;
+
+    #[async_trait]
+    impl JobMiddleware for HoldBack {
+        async fn before(&self, _job: &QueuedJob) -> Action {
+            Action::Release(Duration::ZERO)
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PublishPost {
+        post: String,
+    }
+
+    #[job]
+    #[async_trait]
+    impl Job for PublishPost {
+        async fn handle(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn middleware(&self) -> Vec<Box<dyn JobMiddleware>> {
+            vec![Box::new(HoldBack)]
+        }
+    }
+
+    async fn push<T: Job>(queue: &str, job: &T, delay: Option<Duration>) {
+        let payload = serde_json::to_string(&JobWrapper::new(job).unwrap()).unwrap();
+        Queue::push(queue, payload, delay).await.unwrap();
+    }
+
+    /// Run the worker, failing the test instead of hanging if it never stops
+    async fn run(worker: &Worker) {
+        tokio::time::timeout(Duration::from_secs(5), worker.run())
+            .await
+            .expect("Worker did not stop");
+    }
+
+    #[tokio::test]
+    async fn test_worker_takes_jobs_by_queue_priority() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        RUNS.lock().unwrap().clear();
+        for (queue, name) in [("low", "low-1"), ("default", "default-1"), ("high", "high-1"), ("low", "low-2"), ("high", "high-2")] {
+            push(queue, &SendReport { name: name.to_string() }, None).await;
+        }
+
+        run(&Worker::new("high, default, low").stop_when_empty()).await;
+        assert_eq!(runs(), vec!["high-1", "high-2", "default-1", "low-1", "low-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_when_empty_without_waiting_for_delayed_jobs() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        RUNS.lock().unwrap().clear();
+        push("default", &SendReport { name: "now".to_string() }, None).await;
+        push("default", &SendReport { name: "later".to_string() }, Some(Duration::from_secs(60))).await;
+
+        run(&Worker::new("default").sleep_duration(Duration::from_secs(60)).stop_when_empty()).await;
+        assert_eq!(runs(), vec!["now"]);
+        assert_eq!(Queue::size("default").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_after_max_jobs() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        RUNS.lock().unwrap().clear();
+        for name in ["first", "second", "third"] {
+            push("default", &SendReport { name: name.to_string() }, None).await;
+        }
+
+        run(&Worker::new("default").concurrency(2).max_jobs(2)).await;
+        assert_eq!(runs().len(), 2);
+        assert_eq!(Queue::size("default").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_after_max_time() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        let started = Instant::now();
+
+        run(&Worker::new("default")
+            .sleep_duration(Duration::from_millis(10))
+            .max_time(Duration::from_millis(100)))
+        .await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_jobs_in_progress() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        RUNS.lock().unwrap().clear();
+        for name in ["feed-1", "feed-2"] {
+            push("default", &ImportFeed { name: name.to_string() }, None).await;
+        }
+
+        let worker = Worker::new("default").sleep_duration(Duration::from_millis(10));
+        let shutdown = async {
+            while runs().is_empty() {
+                sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), worker.run_until(shutdown))
+            .await
+            .expect("Worker did not stop");
+
+        assert_eq!(runs(), vec!["feed-1 started", "feed-1 finished"]);
+        assert_eq!(Queue::size("default").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_released_by_middleware_keep_their_attempts() {
+        let _queue = Queue::fake();
+        let _cache = Cache::fake();
+        push("default", &PublishPost { post: "hello-world".to_string() }, None).await;
+
+        let worker = Worker::new("default");
+        for _ in 0..3 {
+            let queued_job = Queue::pop("default").await.unwrap();
+            assert_eq!(queued_job.attempts, 0);
+            worker.process(queued_job).await;
+        }
+    }
+}
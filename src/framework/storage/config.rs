@@ -1,25 +1,50 @@
-use std::path::PathBuf;
-use serde::{Deserialize, Serialize};
-use crate::framework::storage::drivers::LocalDriver;
-use crate::framework::storage::STORAGE_DRIVER;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::framework::aws::Credentials;
+use crate::framework::storage::drivers::{LocalDriver, S3Driver};
+use crate::framework::storage::{Disk, StorageDriver, STORAGE_DISKS, STORAGE_DRIVER};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::env;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
+    /// The name of the disk used by the `Storage` facade's static methods
     #[serde(default = "default_driver")]
     pub default: String,
-    pub disks: Disks,
+    #[serde(default = "default_disks")]
+    pub disks: HashMap<String, DiskConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Disks {
-    #[serde(default = "default_local_config")]
-    pub local: LocalDiskConfig,
-    // Uncomment when implementing S3 and R2 drivers
-    // pub s3: S3DiskConfig,
-    // pub r2: R2DiskConfig,
+/// A disk, with its driver named by the `driver` key in `config/storage.toml`. Disks without
+/// one use the local driver, as they did before other drivers existed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "driver", rename_all = "lowercase")]
+pub enum DiskConfig {
+    Local(LocalDiskConfig),
+    /// Amazon S3 or an S3-compatible service such as Cloudflare R2 or MinIO
+    S3(S3DiskConfig),
+}
+
+/// The shape `DiskConfig` is read in once the driver is filled in
+#[derive(Deserialize)]
+#[serde(tag = "driver", rename_all = "lowercase")]
+enum TaggedDiskConfig {
+    Local(LocalDiskConfig),
+    S3(S3DiskConfig),
+}
+
+impl<'de> Deserialize<'de> for DiskConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = toml::Table::deserialize(deserializer)?;
+        table.entry("driver").or_insert_with(|| "local".into());
+        let config = TaggedDiskConfig::deserialize(toml::Value::Table(table)).map_err(serde::de::Error::custom)?;
+        Ok(match config {
+            TaggedDiskConfig::Local(local) => DiskConfig::Local(local),
+            TaggedDiskConfig::S3(s3) => DiskConfig::S3(s3),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,27 +55,21 @@ pub struct LocalDiskConfig {
     pub url: String,
 }
 
-// Example S3 configuration for future implementation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S3DiskConfig {
-    pub key: String,
-    pub secret: String,
+    /// The access key ID, falling back to `AWS_ACCESS_KEY_ID`
+    pub key: Option<String>,
+    /// The secret access key, falling back to `AWS_SECRET_ACCESS_KEY`
+    pub secret: Option<String>,
+    #[serde(default = "default_region")]
     pub region: String,
     pub bucket: String,
+    /// The public base URL of the bucket
     pub url: Option<String>,
+    /// The endpoint of an S3-compatible service
     pub endpoint: Option<String>,
 }
 
-// Example R2 configuration for future implementation
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct R2DiskConfig {
-    pub account_id: String,
-    pub access_key_id: String,
-    pub secret_access_key: String,
-    pub bucket: String,
-    pub url: Option<String>,
-}
-
 fn default_driver() -> String {
     env::var("STORAGE_DRIVER").unwrap_or_else(|_| "local".to_string())
 }
@@ -63,6 +82,10 @@ fn default_storage_url() -> String {
     env::var("STORAGE_URL").unwrap_or_else(|_| "http://localhost:3000/storage".to_string())
 }
 
+fn default_region() -> String {
+    env::var("AWS_DEFAULT_REGION").unwrap_or_else(|_| "us-east-1".to_string())
+}
+
 fn default_local_config() -> LocalDiskConfig {
     LocalDiskConfig {
         root: default_storage_path(),
@@ -70,31 +93,123 @@ fn default_local_config() -> LocalDiskConfig {
     }
 }
 
+fn default_disks() -> HashMap<String, DiskConfig> {
+    HashMap::from([("local".to_string(), DiskConfig::Local(default_local_config()))])
+}
+
 impl Default for StorageConfig {
     fn default() -> Self {
         Self {
             default: default_driver(),
-            disks: Disks {
-                local: default_local_config(),
-            },
+            disks: default_disks(),
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Load `config/storage.toml`, falling back to the environment when the file doesn't exist
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = Path::new("config/storage.toml");
+        if path.exists() {
+            Self::from_file(path)
+        } else {
+            Ok(Self::default())
         }
     }
+
+    /// Load the storage configuration from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(toml::from_str(&contents)?)
+    }
+}
+
+/// Create the driver for a disk
+async fn build_driver(config: &DiskConfig) -> Result<Box<dyn StorageDriver + Send + Sync>, Box<dyn std::error::Error>> {
+    Ok(match config {
+        DiskConfig::Local(local) => Box::new(LocalDriver::new(&local.root, &local.url).await?),
+        DiskConfig::S3(s3) => {
+            let mut driver = S3Driver::new(&s3.bucket, &s3.region);
+            if let (Some(key), Some(secret)) = (&s3.key, &s3.secret) {
+                driver = driver.credentials(Credentials::new(key, secret));
+            }
+            if let Some(endpoint) = &s3.endpoint {
+                driver = driver.endpoint(endpoint);
+            }
+            if let Some(url) = &s3.url {
+                driver = driver.public_url(url);
+            }
+            Box::new(driver)
+        }
+    })
+}
+
+/// Create every configured disk
+async fn build_disks(config: &StorageConfig) -> Result<HashMap<String, Disk>, Box<dyn std::error::Error>> {
+    let mut disks = HashMap::new();
+    for (name, disk) in &config.disks {
+        let driver = build_driver(disk)
+            .await
+            .map_err(|e| format!("Failed to create storage disk [{}]: {}", name, e))?;
+        disks.insert(name.clone(), Disk::new(Arc::new(RwLock::new(driver))));
+    }
+    Ok(disks)
 }
 
 /// Initialize the storage system with the provided configuration
 pub async fn init_storage(config: StorageConfig) -> Result<(), Box<dyn std::error::Error>> {
-    let driver: Box<dyn crate::framework::storage::StorageDriver + Send + Sync> = match config.default.as_str() {
-        "local" => {
-            Box::new(LocalDriver::new(
-                config.disks.local.root,
-                &config.disks.local.url,
-            ).await?)
-        }
-        // Add other drivers here when implemented
-        _ => return Err(format!("Unsupported storage driver: {}", config.default).into()),
-    };
+    let disks = build_disks(&config).await?;
+    let default = disks
+        .get(&config.default)
+        .ok_or_else(|| format!("The default storage disk [{}] is not defined", config.default))?
+        .driver();
 
+    STORAGE_DISKS
+        .set(disks)
+        .map_err(|_| "Failed to initialize storage disks")?;
     STORAGE_DRIVER
-        .set(Arc::new(RwLock::new(driver)))
+        .set(default)
         .map_err(|_| "Failed to initialize storage driver".into())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builds_named_disks() {
+        let root = env::temp_dir().join(format!("ruskit-storage-{}", uuid::Uuid::new_v4()));
+        let config: StorageConfig = toml::from_str(&format!(
+            r#"
+            default = "local"
+
+            [disks.local]
+            driver = "local"
+            root = "{root}/app"
+
+            [disks.public]
+            root = "{root}/public"
+            url = "https://example.com/storage"
+
+            [disks.s3]
+            driver = "s3"
+            bucket = "uploads"
+            region = "auto"
+            endpoint = "https://account.r2.cloudflarestorage.com"
+            "#,
+            root = root.display()
+        ))
+        .unwrap();
+
+        assert!(matches!(&config.disks["s3"], DiskConfig::S3(s3) if s3.bucket == "uploads" && s3.key.is_none()));
+        let disks = build_disks(&config).await.unwrap();
+        disks["public"].put("avatars/1.txt", b"jane").await.unwrap();
+        assert_eq!(disks["public"].get("avatars/1.txt").await.unwrap(), b"jane");
+        assert!(!disks["local"].exists("avatars/1.txt").await);
+        assert_eq!(disks["public"].url("avatars/1.txt").await, "https://example.com/storage/avatars/1.txt");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+use crate::framework::storage::StorageDriver;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+type SharedDriver = Arc<RwLock<Box<dyn StorageDriver + Send + Sync>>>;
+
+/// The file operations for a single disk. The `Storage` facade uses the default disk,
+/// and `Storage::disk` gives access to the others.
+#[derive(Clone)]
+pub struct Disk {
+    driver: SharedDriver,
+}
+
+impl Disk {
+    pub fn new(driver: SharedDriver) -> Self {
+        Self { driver }
+    }
+
+    /// Get the underlying storage driver
+    pub fn driver(&self) -> SharedDriver {
+        Arc::clone(&self.driver)
+    }
+
+    /// Get the contents of a file
+    pub async fn get(&self, path: &str) -> IoResult<Vec<u8>> {
+        self.driver.read().await.get(path).await
+    }
+
+    /// Write the contents of a file
+    pub async fn put(&self, path: &str, contents: &[u8]) -> IoResult<()> {
+        self.driver.read().await.put(path, contents).await
+    }
+
+    /// Delete the file at a given path
+    pub async fn delete(&self, path: &str) -> IoResult<()> {
+        self.driver.read().await.delete(path).await
+    }
+
+    /// Determine if a file exists
+    pub async fn exists(&self, path: &str) -> bool {
+        self.driver.read().await.exists(path).await
+    }
+
+    /// Get the size of a file in bytes
+    pub async fn size(&self, path: &str) -> IoResult<u64> {
+        self.driver.read().await.size(path).await
+    }
+
+    /// Copy a file to a new location
+    pub async fn copy(&self, from: &str, to: &str) -> IoResult<()> {
+        self.driver.read().await.copy(from, to).await
+    }
+
+    /// Move a file to a new location
+    pub async fn move_file(&self, from: &str, to: &str) -> IoResult<()> {
+        self.driver.read().await.move_file(from, to).await
+    }
+
+    /// Get a URL for the file at the given path
+    pub async fn url(&self, path: &str) -> String {
+        self.driver.read().await.url(path).await
+    }
+
+    /// Create a directory at the given path
+    pub async fn make_directory(&self, path: &str) -> IoResult<()> {
+        self.driver.read().await.make_directory(path).await
+    }
+
+    /// Delete a directory at the given path
+    pub async fn delete_directory(&self, path: &str) -> IoResult<()> {
+        self.driver.read().await.delete_directory(path).await
+    }
+}
@@ -1,9 +1,5 @@
 mod local;
+mod s3;
 
 pub use local::LocalDriver;
-
-// Future drivers will be added here
-// mod s3;
-// mod r2;
-// pub use s3::S3Driver;
-// pub use r2::R2Driver; 
\ No newline at end of file
+pub use s3::S3Driver;
@@ -0,0 +1,294 @@
+use crate::framework::aws::{uri_encode, Credentials, SignatureV4};
+use crate::framework::storage::StorageDriver;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+
+/// Stores files in an S3 bucket or an S3-compatible service such as Cloudflare R2 or MinIO.
+/// Requests use path-style URLs, `{endpoint}/{bucket}/{path}`.
+#[derive(Debug)]
+pub struct S3Driver {
+    client: reqwest::Client,
+    credentials: Option<Credentials>,
+    region: String,
+    bucket: String,
+    endpoint: String,
+    url: Option<String>,
+}
+
+impl S3Driver {
+    /// Create a driver for a bucket on AWS, signing requests with the credentials in the environment
+    pub fn new(bucket: &str, region: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            credentials: Credentials::from_env(),
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            endpoint: format!("https://s3.{}.amazonaws.com", region),
+            url: None,
+        }
+    }
+
+    /// Send requests to an S3-compatible service instead of AWS
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sign requests with these credentials instead of the ones in the environment
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// The public base URL of the bucket, e.g. a CDN in front of it
+    pub fn public_url(mut self, url: &str) -> Self {
+        self.url = Some(url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The path of an object within the bucket, with each segment encoded as S3 expects
+    fn object_path(&self, path: &str) -> String {
+        let key = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("/{}/{}", uri_encode(&self.bucket), key)
+    }
+
+    async fn send(&self, method: Method, path_and_query: &str, extra_headers: Vec<(String, String)>, body: Vec<u8>) -> IoResult<reqwest::Response> {
+        let credentials = self
+            .credentials
+            .clone()
+            .ok_or_else(|| IoError::other("AWS credentials not configured for the S3 driver"))?;
+        let url = Url::parse(&format!("{}{}", self.endpoint, path_and_query)).map_err(IoError::other)?;
+
+        let mut headers = extra_headers;
+        headers.push(("x-amz-content-sha256".to_string(), hex::encode(Sha256::digest(&body))));
+        SignatureV4::new(credentials, &self.region, "s3").sign(method.as_str(), &url, &mut headers, &body, Utc::now());
+
+        let mut request = self.client.request(method, url).body(body);
+        for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await.map_err(IoError::other)?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(IoError::new(ErrorKind::NotFound, format!("{} not found", path_and_query))),
+            StatusCode::FORBIDDEN => Err(IoError::new(ErrorKind::PermissionDenied, response.text().await.unwrap_or_default())),
+            status => Err(IoError::other(format!(
+                "S3 request failed ({}): {}",
+                status,
+                response.text().await.unwrap_or_default()
+            ))),
+        }
+    }
+
+    /// List the keys of every object under a prefix
+    async fn list(&self, prefix: &str) -> IoResult<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut query = format!("/{}?list-type=2&prefix={}", uri_encode(&self.bucket), uri_encode(prefix));
+            if let Some(token) = &continuation {
+                query.push_str(&format!("&continuation-token={}", uri_encode(token)));
+            }
+            let body = self.send(Method::GET, &query, Vec::new(), Vec::new()).await?.text().await.map_err(IoError::other)?;
+
+            keys.extend(xml_values(&body, "Key"));
+            continuation = xml_values(&body, "NextContinuationToken").into_iter().next();
+            if continuation.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+}
+
+/// The text of every `<tag>` element in a ListObjectsV2 response
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        values.push(
+            rest[..end]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        );
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+#[async_trait]
+impl StorageDriver for S3Driver {
+    async fn get(&self, path: &str) -> IoResult<Vec<u8>> {
+        let response = self.send(Method::GET, &self.object_path(path), Vec::new(), Vec::new()).await?;
+        Ok(response.bytes().await.map_err(IoError::other)?.to_vec())
+    }
+
+    async fn put(&self, path: &str, contents: &[u8]) -> IoResult<()> {
+        self.send(Method::PUT, &self.object_path(path), Vec::new(), contents.to_vec()).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, path: &str) -> IoResult<()> {
+        self.send(Method::DELETE, &self.object_path(path), Vec::new(), Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn exists(&self, path: &str) -> bool {
+        self.send(Method::HEAD, &self.object_path(path), Vec::new(), Vec::new()).await.is_ok()
+    }
+
+    async fn size(&self, path: &str) -> IoResult<u64> {
+        let response = self.send(Method::HEAD, &self.object_path(path), Vec::new(), Vec::new()).await?;
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok())
+            .ok_or_else(|| IoError::other("S3 did not return the object size"))
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> IoResult<()> {
+        let source = vec![("x-amz-copy-source".to_string(), self.object_path(from))];
+        self.send(Method::PUT, &self.object_path(to), source, Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn move_file(&self, from: &str, to: &str) -> IoResult<()> {
+        self.copy(from, to).await?;
+        self.delete(from).await
+    }
+
+    async fn url(&self, path: &str) -> String {
+        match &self.url {
+            Some(url) => format!("{}/{}", url, path.trim_start_matches('/')),
+            None => format!("{}{}", self.endpoint, self.object_path(path)),
+        }
+    }
+
+    async fn make_directory(&self, _path: &str) -> IoResult<()> {
+        // Buckets have no directories, keys with a common prefix appear as one
+        Ok(())
+    }
+
+    async fn delete_directory(&self, path: &str) -> IoResult<()> {
+        let prefix = format!("{}/", path.trim_matches('/'));
+        for key in self.list(&prefix).await? {
+            self.delete(&key).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, Method as HttpMethod, StatusCode as HttpStatus, Uri};
+    use axum::Router;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    /// An in-memory stand-in for S3 that checks every request is signed
+    async fn mock_s3() -> String {
+        let objects: Arc<Mutex<BTreeMap<String, Vec<u8>>>> = Arc::default();
+        let app = Router::new().fallback(move |method: HttpMethod, uri: Uri, headers: HeaderMap, body: Bytes| {
+            let objects = objects.clone();
+            async move {
+                let authorization = headers["authorization"].to_str().unwrap();
+                assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+                assert!(authorization.contains("/eu-west-1/s3/aws4_request"));
+                assert_eq!(headers["x-amz-content-sha256"].to_str().unwrap(), hex::encode(Sha256::digest(&body)));
+
+                let mut objects = objects.lock().unwrap();
+                let path = uri.path().to_string();
+                match method {
+                    HttpMethod::PUT => {
+                        let contents = match headers.get("x-amz-copy-source") {
+                            Some(source) => objects[source.to_str().unwrap()].clone(),
+                            None => body.to_vec(),
+                        };
+                        objects.insert(path, contents);
+                        (HttpStatus::OK, Vec::new())
+                    }
+                    HttpMethod::GET if uri.query().is_some_and(|query| query.contains("list-type=2")) => {
+                        let keys: String = objects
+                            .keys()
+                            .map(|key| format!("<Contents><Key>{}</Key></Contents>", key.trim_start_matches("/uploads/")))
+                            .collect();
+                        (HttpStatus::OK, format!("<ListBucketResult>{}</ListBucketResult>", keys).into_bytes())
+                    }
+                    HttpMethod::GET | HttpMethod::HEAD => match objects.get(&path) {
+                        Some(contents) => (HttpStatus::OK, contents.clone()),
+                        None => (HttpStatus::NOT_FOUND, Vec::new()),
+                    },
+                    HttpMethod::DELETE => {
+                        objects.remove(&path);
+                        (HttpStatus::NO_CONTENT, Vec::new())
+                    }
+                    other => panic!("unexpected method {}", other),
+                }
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_s3_disk_roundtrip() {
+        let driver = S3Driver::new("uploads", "eu-west-1")
+            .endpoint(&mock_s3().await)
+            .credentials(Credentials::new("AKIDEXAMPLE", "secret"));
+
+        driver.put("avatars/jane.png", b"jane").await.unwrap();
+        assert!(driver.exists("avatars/jane.png").await);
+        assert_eq!(driver.get("avatars/jane.png").await.unwrap(), b"jane");
+        assert_eq!(driver.size("avatars/jane.png").await.unwrap(), 4);
+
+        driver.move_file("avatars/jane.png", "avatars/jane-doe.png").await.unwrap();
+        assert!(!driver.exists("avatars/jane.png").await);
+        assert_eq!(driver.get("avatars/jane-doe.png").await.unwrap(), b"jane");
+        let missing = driver.get("avatars/jane.png").await.unwrap_err();
+        assert_eq!(missing.kind(), ErrorKind::NotFound);
+
+        driver.put("avatars/john.png", b"john").await.unwrap();
+        driver.delete_directory("avatars").await.unwrap();
+        assert!(!driver.exists("avatars/jane-doe.png").await);
+        assert!(!driver.exists("avatars/john.png").await);
+    }
+
+    #[tokio::test]
+    async fn test_builds_object_urls_and_reads_listings() {
+        let driver = S3Driver::new("uploads", "eu-west-1");
+        assert_eq!(driver.object_path("/avatars/jane doe.png"), "/uploads/avatars/jane%20doe.png");
+        assert_eq!(
+            StorageDriver::url(&driver, "avatars/1.png").await,
+            "https://s3.eu-west-1.amazonaws.com/uploads/avatars/1.png"
+        );
+        let driver = driver.endpoint("http://localhost:9000/").public_url("https://cdn.example.com/");
+        assert_eq!(StorageDriver::url(&driver, "/avatars/1.png").await, "https://cdn.example.com/avatars/1.png");
+
+        let listing = "<ListBucketResult><Contents><Key>a/1.txt</Key></Contents>\
+                       <Contents><Key>a/R&amp;D.txt</Key></Contents>\
+                       <NextContinuationToken>abc</NextContinuationToken></ListBucketResult>";
+        assert_eq!(xml_values(listing, "Key"), vec!["a/1.txt", "a/R&D.txt"]);
+        assert_eq!(xml_values(listing, "NextContinuationToken"), vec!["abc"]);
+    }
+}
@@ -6,11 +6,16 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use once_cell::sync::OnceCell;
 use std::fmt::Debug;
+use std::collections::HashMap;
 
 pub mod drivers;
 pub mod config;
+pub mod disk;
+
+pub use disk::Disk;
 
 static STORAGE_DRIVER: OnceCell<Arc<RwLock<Box<dyn StorageDriver + Send + Sync>>>> = OnceCell::new();
+static STORAGE_DISKS: OnceCell<HashMap<String, Disk>> = OnceCell::new();
 
 #[async_trait]
 pub trait StorageDriver: Debug {
@@ -54,73 +59,63 @@ impl Storage {
         Arc::clone(STORAGE_DRIVER.get().expect("Storage driver not initialized"))
     }
 
+    /// Get one of the disks configured under `disks`, e.g. `Storage::disk("public")`, or
+    /// `None` if no disk has that name
+    pub fn disk(name: &str) -> Option<Disk> {
+        STORAGE_DISKS.get().and_then(|disks| disks.get(name)).cloned()
+    }
+
+    fn default_disk() -> Disk {
+        Disk::new(Self::driver())
+    }
+
     /// Get the contents of a file
     pub async fn get(path: &str) -> IoResult<Vec<u8>> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.get(path).await
+        Self::default_disk().get(path).await
     }
 
     /// Write the contents of a file
     pub async fn put(path: &str, contents: &[u8]) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.put(path, contents).await
+        Self::default_disk().put(path, contents).await
     }
 
     /// Delete the file at a given path
     pub async fn delete(path: &str) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.delete(path).await
+        Self::default_disk().delete(path).await
     }
 
     /// Determine if a file exists
     pub async fn exists(path: &str) -> bool {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.exists(path).await
+        Self::default_disk().exists(path).await
     }
 
     /// Get the size of a file in bytes
     pub async fn size(path: &str) -> IoResult<u64> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.size(path).await
+        Self::default_disk().size(path).await
     }
 
     /// Copy a file to a new location
     pub async fn copy(from: &str, to: &str) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.copy(from, to).await
+        Self::default_disk().copy(from, to).await
     }
 
     /// Move a file to a new location
     pub async fn move_file(from: &str, to: &str) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.move_file(from, to).await
+        Self::default_disk().move_file(from, to).await
     }
 
     /// Get a URL for the file at the given path
     pub async fn url(path: &str) -> String {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.url(path).await
+        Self::default_disk().url(path).await
     }
 
     /// Create a directory at the given path
     pub async fn make_directory(path: &str) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.make_directory(path).await
+        Self::default_disk().make_directory(path).await
     }
 
     /// Delete a directory at the given path
     pub async fn delete_directory(path: &str) -> IoResult<()> {
-        let driver = Self::driver();
-        let driver = driver.read().await;
-        driver.delete_directory(path).await
+        Self::default_disk().delete_directory(path).await
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,67 @@
+use crate::framework::cache::Cache;
+use serde::Serialize;
+
+/// Assert that the cache holds an item for the key
+pub async fn assert_cache_has(key: &str) {
+    assert!(Cache::has(key).await, "Expected the cache to have key {}", key);
+}
+
+/// Assert that the cache holds no item for the key
+pub async fn assert_cache_missing(key: &str) {
+    assert!(!Cache::has(key).await, "Expected the cache not to have key {}", key);
+}
+
+/// Assert that the value was written under the key while the cache was faked
+pub fn assert_cache_put<T: Serialize>(key: &str, value: T) {
+    let value = serde_json::to_value(value).expect("The expected value could not be serialized");
+    let written = Cache::written();
+    assert!(
+        written.iter().any(|(k, v)| k == key && *v == value),
+        "Expected {} to be written to the cache under {}, but it was written with {:?}",
+        value,
+        key,
+        written.iter().filter(|(k, _)| k == key).map(|(_, v)| v).collect::<Vec<_>>()
+    );
+}
+
+/// Assert that nothing was written under the key while the cache was faked
+pub fn assert_cache_not_put(key: &str) {
+    assert!(
+        Cache::written().iter().all(|(k, _)| k != key),
+        "Expected nothing to be written to the cache under {}",
+        key
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cache_fake_records_writes() {
+        let fake = Cache::fake();
+        assert_cache_missing("users:count").await;
+
+        let count = Cache::remember("users:count", Duration::from_secs(60), || 3).await;
+        assert_eq!(count, Some(3));
+        let stale = Cache::flexible("users:all", Duration::from_secs(60), Duration::from_secs(60), || async {
+            vec!["jane".to_string()]
+        })
+        .await;
+        assert_eq!(stale, Some(vec!["jane".to_string()]));
+
+        assert_cache_has("users:count").await;
+        assert_cache_put("users:count", 3);
+        // A flexible value and its creation time are written under the one key
+        let written = Cache::written();
+        assert_eq!(written.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["users:count", "users:all"]);
+        assert_eq!(written[1].1["value"], serde_json::json!(["jane"]));
+        assert_cache_not_put("users:missing");
+        assert_eq!(Cache::store_named("reports").get::<u32>("users:count").await, Some(3));
+
+        drop(fake);
+        let _fake = Cache::fake();
+        assert_cache_missing("users:count").await;
+    }
+}
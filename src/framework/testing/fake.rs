@@ -0,0 +1,55 @@
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock};
+
+/// Holds the fake installed for a facade.
+///
+/// The fake is shared by the whole process, so jobs and handlers the code under test spawns
+/// onto other threads see it too. Installing a fake waits until no other test holds one for
+/// the same facade, which keeps tests that fake it from running at the same time.
+pub struct FakeSlot<T> {
+    serial: Mutex<()>,
+    current: RwLock<Option<T>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> FakeSlot<T> {
+    pub const fn new() -> Self {
+        Self {
+            serial: Mutex::new(()),
+            current: RwLock::new(None),
+        }
+    }
+
+    /// Install a fake until the returned guard is dropped
+    pub fn install(&'static self, fake: T) -> FakeGuard {
+        // A test that panicked while holding the fake still released it when its guard dropped
+        let serial = self.serial.lock().unwrap_or_else(PoisonError::into_inner);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Some(fake);
+        FakeGuard {
+            clear: Box::new(|| *self.current.write().unwrap_or_else(PoisonError::into_inner) = None),
+            _serial: serial,
+        }
+    }
+
+    /// The installed fake, if any
+    pub fn current(&self) -> Option<T> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+/// Removes a fake when dropped, restoring the real backend of its facade
+#[must_use = "the fake is removed as soon as the guard is dropped"]
+pub struct FakeGuard {
+    clear: Box<dyn Fn() + Send + Sync>,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Default for FakeSlot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FakeGuard {
+    fn drop(&mut self) {
+        (self.clear)();
+    }
+}
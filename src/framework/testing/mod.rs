@@ -12,9 +12,18 @@ use axum::body::Bytes;
 use std::collections::HashMap;
 
 pub mod assertions;
+pub mod cache;
 pub mod database;
+pub mod fake;
 pub mod http;
 
+// Fakes live with their facades so the facades can swap their backends
+pub use crate::framework::queue::fake::{FakeDriver, PushedJob};
+pub use crate::framework::cache::fake::FakeStore;
+pub use database::{create_table, sqlite_database};
+pub use fake::FakeGuard;
+pub use cache::{assert_cache_has, assert_cache_missing, assert_cache_put, assert_cache_not_put};
+
 /// Helper function to read the entire body into bytes
 pub async fn read_body(body: Body) -> Vec<u8> {
     let bytes = to_bytes(body, usize::MAX).await.unwrap_or_default();
@@ -90,22 +99,9 @@ impl TestCase {
         self.assert_status(StatusCode::CREATED)
     }
 
-    /// Get the response body bytes, reading them if necessary
+    /// Get the response body bytes, read when the request was sent
     fn get_body_bytes(&mut self) -> Vec<u8> {
-        if let Some(bytes) = self.response_bytes.take() {
-            return bytes;
-        }
-
-        let response = self.response.as_mut().unwrap();
-        let bytes = tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(async {
-                let body = std::mem::replace(response.body_mut(), Body::empty());
-                read_body(body).await
-            });
-        
-        self.response_bytes = Some(bytes.clone());
-        bytes
+        self.response_bytes.clone().unwrap_or_default()
     }
 
     /// Assert response matches JSON
@@ -154,12 +150,15 @@ impl TestCase {
             .body(body)
             .unwrap();
 
-        let response = self.app
+        let mut response = self.app
             .clone()
             .oneshot(request)
             .await
             .unwrap();
 
+        // Read the body now, the assertions are synchronous and can't await it
+        let body = std::mem::replace(response.body_mut(), Body::empty());
+        self.response_bytes = Some(read_body(body).await);
         self.response = Some(response);
         self
     }
@@ -212,11 +211,9 @@ mod tests {
             axum::Json(json!({"message": "success"}))
         }));
         
-        test(app)
-            .get("/")
-            .await
-            .assert_ok()
-            .assert_json(json!({"message": "success"}))
+        let mut case = test(app);
+        case.get("/").await.assert_ok();
+        case.assert_json(json!({"message": "success"}))
             .assert_json_has("message");
     }
 } 
\ No newline at end of file